@@ -0,0 +1,414 @@
+//! # Button Gestures
+//! Recognises short, long and double presses of the alarm button.
+//!
+//! [`GestureDetector`] is fed the raw edges of the button and debounces them
+//! by ignoring any edge within [`DEBOUNCE`] of the last one. Since a short
+//! press could still become a double press, it is only reported once
+//! [`DOUBLE_PRESS_GAP`] has passed without a second press.
+//!
+//! Each gesture is mapped to a [`ButtonAction`] through a [`ButtonMap`].
+
+use core::str::FromStr;
+
+use embassy_time::{Duration, Instant};
+
+/// Edges closer than this to the last edge are contact bounce.
+const DEBOUNCE: Duration = Duration::from_millis(30);
+
+/// How long the button must be held for a long press.
+const LONG_PRESS: Duration = Duration::from_millis(1000);
+
+/// How soon after a short press the button must be pressed again for a double press.
+const DOUBLE_PRESS_GAP: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Edge {
+    Press,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Gesture {
+    Short,
+    Long,
+    Double,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[error("Unknown gesture")]
+pub struct UnknownGesture;
+
+impl Gesture {
+    pub const ALL: [Self; 3] = [Self::Short, Self::Long, Self::Double];
+
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Short => "short",
+            Self::Long => "long",
+            Self::Double => "double",
+        }
+    }
+}
+
+impl FromStr for Gesture {
+    type Err = UnknownGesture;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|g| g.name().eq_ignore_ascii_case(s))
+            .ok_or(UnknownGesture)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    /// Pressed for the first time.
+    Pressed {
+        since: Instant,
+    },
+    /// Long press was reported and waiting for the release.
+    Held,
+    /// Released after a short press and waiting for a second press.
+    Released {
+        at: Instant,
+    },
+    /// Pressed for the second time.
+    PressedAgain,
+}
+
+/// Turns debounced button edges into [`Gesture`]s.
+///
+/// [`GestureDetector::timeout`] must be called once [`GestureDetector::deadline`]
+/// passes, since long and short presses are reported without an edge.
+pub struct GestureDetector {
+    state: State,
+    last_edge: Option<Instant>,
+}
+
+impl GestureDetector {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            state: State::Idle,
+            last_edge: None,
+        }
+    }
+
+    /// Handles an edge of the button at `now`.
+    ///
+    /// Bounces and edges that do not fit the current state, e.g. after a missed edge, are ignored.
+    pub fn edge(&mut self, edge: Edge, now: Instant) -> Option<Gesture> {
+        if self
+            .last_edge
+            .is_some_and(|last| now.saturating_duration_since(last) < DEBOUNCE)
+        {
+            return None;
+        }
+
+        let (state, gesture) = match (self.state, edge) {
+            (State::Idle, Edge::Press) => (State::Pressed { since: now }, None),
+            (State::Pressed { since }, Edge::Release) => {
+                if now.saturating_duration_since(since) >= LONG_PRESS {
+                    (State::Idle, Some(Gesture::Long))
+                } else {
+                    (State::Released { at: now }, None)
+                }
+            }
+            (State::Held, Edge::Release) => (State::Idle, None),
+            (State::Released { .. }, Edge::Press) => (State::PressedAgain, None),
+            (State::PressedAgain, Edge::Release) => (State::Idle, Some(Gesture::Double)),
+            _ => return None,
+        };
+
+        self.state = state;
+        self.last_edge = Some(now);
+        gesture
+    }
+
+    /// Returns when [`GestureDetector::timeout`] should be called next.
+    pub fn deadline(&self) -> Option<Instant> {
+        let (from, after) = match self.state {
+            State::Pressed { since } => (since, LONG_PRESS),
+            State::Released { at } => (at, DOUBLE_PRESS_GAP),
+            State::Idle | State::Held | State::PressedAgain => return None,
+        };
+
+        Some(from.checked_add(after).unwrap_or(Instant::MAX))
+    }
+
+    /// Reports a long press while still held, or a short press
+    /// once too much time has passed for a double press.
+    pub fn timeout(&mut self, now: Instant) -> Option<Gesture> {
+        if self.deadline().is_none_or(|deadline| now < deadline) {
+            return None;
+        }
+
+        match self.state {
+            State::Pressed { .. } => {
+                self.state = State::Held;
+                Some(Gesture::Long)
+            }
+            State::Released { .. } => {
+                self.state = State::Idle;
+                Some(Gesture::Short)
+            }
+            State::Idle | State::Held | State::PressedAgain => None,
+        }
+    }
+}
+
+impl Default for GestureDetector {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// What a [`Gesture`] does.
+pub enum ButtonAction {
+    /// Does nothing.
+    Ignore,
+    /// Snoozes the ringing alarm.
+    Snooze,
+    /// Dismisses the ringing alarm.
+    Dismiss,
+    /// Toggles the LCD backlight.
+    Backlight,
+    /// Starts the last started timer again.
+    RestartTimer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[error("Unknown button action")]
+pub struct UnknownButtonAction;
+
+impl ButtonAction {
+    pub const ALL: [Self; 5] = [
+        Self::Ignore,
+        Self::Snooze,
+        Self::Dismiss,
+        Self::Backlight,
+        Self::RestartTimer,
+    ];
+
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Ignore => "ignore",
+            Self::Snooze => "snooze",
+            Self::Dismiss => "dismiss",
+            Self::Backlight => "backlight",
+            Self::RestartTimer => "restart-timer",
+        }
+    }
+}
+
+impl FromStr for ButtonAction {
+    type Err = UnknownButtonAction;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.name().eq_ignore_ascii_case(s))
+            .ok_or(UnknownButtonAction)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// The action of each [`Gesture`].
+pub struct ButtonMap {
+    pub short: ButtonAction,
+    pub long: ButtonAction,
+    pub double: ButtonAction,
+}
+
+impl ButtonMap {
+    #[inline]
+    pub fn get(self, gesture: Gesture) -> ButtonAction {
+        match gesture {
+            Gesture::Short => self.short,
+            Gesture::Long => self.long,
+            Gesture::Double => self.double,
+        }
+    }
+
+    #[inline]
+    pub fn set(&mut self, gesture: Gesture, action: ButtonAction) {
+        match gesture {
+            Gesture::Short => self.short = action,
+            Gesture::Long => self.long = action,
+            Gesture::Double => self.double = action,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ms: u64) -> Instant {
+        Instant::from_millis(ms)
+    }
+
+    /// Reports the gestures that time out by `now`, like the button task would.
+    fn drain(detector: &mut GestureDetector, now: Instant, gestures: &mut Gestures) {
+        while let Some(deadline) = detector.deadline().filter(|d| *d <= now) {
+            if let Some(gesture) = detector.timeout(deadline) {
+                gestures.push((deadline.as_millis(), gesture)).unwrap();
+            }
+        }
+    }
+
+    type Gestures = heapless::Vec<(u64, Gesture), 8>;
+
+    /// Feeds `(ms, edge)` pairs in order and collects the gestures until `until`.
+    fn run(edges: &[(u64, Edge)], until: u64) -> Gestures {
+        let mut detector = GestureDetector::new();
+        let mut gestures = heapless::Vec::new();
+
+        for &(ms, edge) in edges {
+            drain(&mut detector, at(ms), &mut gestures);
+            if let Some(gesture) = detector.edge(edge, at(ms)) {
+                gestures.push((ms, gesture)).unwrap();
+            }
+        }
+        drain(&mut detector, at(until), &mut gestures);
+
+        gestures
+    }
+
+    #[test]
+    fn short_press() {
+        let gestures = run(&[(0, Edge::Press), (120, Edge::Release)], 2000);
+        assert_eq!(
+            gestures.as_slice(),
+            &[(420, Gesture::Short)],
+            "reported after the double press gap"
+        );
+    }
+
+    #[test]
+    fn long_press() {
+        let gestures = run(&[(0, Edge::Press), (3000, Edge::Release)], 5000);
+        assert_eq!(
+            gestures.as_slice(),
+            &[(1000, Gesture::Long)],
+            "reported while held and not again on release"
+        );
+    }
+
+    #[test]
+    fn long_press_without_timeout() {
+        let mut detector = GestureDetector::new();
+        detector.edge(Edge::Press, at(0));
+
+        assert_eq!(
+            detector.edge(Edge::Release, at(1500)),
+            Some(Gesture::Long),
+            "release after a missed timeout is still a long press"
+        );
+    }
+
+    #[test]
+    fn double_press() {
+        let gestures = run(
+            &[
+                (0, Edge::Press),
+                (100, Edge::Release),
+                (250, Edge::Press),
+                (350, Edge::Release),
+            ],
+            2000,
+        );
+        assert_eq!(
+            gestures.as_slice(),
+            &[(350, Gesture::Double)],
+            "no short press is reported"
+        );
+    }
+
+    #[test]
+    fn slow_presses_are_two_short_presses() {
+        let gestures = run(
+            &[
+                (0, Edge::Press),
+                (100, Edge::Release),
+                (500, Edge::Press),
+                (600, Edge::Release),
+            ],
+            2000,
+        );
+        assert_eq!(
+            gestures.as_slice(),
+            &[(400, Gesture::Short), (900, Gesture::Short)],
+            "second press came after the gap"
+        );
+    }
+
+    #[test]
+    fn bounces_are_ignored() {
+        let gestures = run(
+            &[
+                (0, Edge::Press),
+                (3, Edge::Release),
+                (6, Edge::Press),
+                (10, Edge::Release),
+                (150, Edge::Release),
+                (155, Edge::Press),
+                (160, Edge::Release),
+            ],
+            2000,
+        );
+        assert_eq!(
+            gestures.as_slice(),
+            &[(450, Gesture::Short)],
+            "bounces on press and release are a single short press"
+        );
+    }
+
+    #[test]
+    fn missed_edges_are_ignored() {
+        let gestures = run(
+            &[
+                (0, Edge::Release),
+                (100, Edge::Press),
+                (200, Edge::Press),
+                (300, Edge::Release),
+            ],
+            2000,
+        );
+        assert_eq!(
+            gestures.as_slice(),
+            &[(600, Gesture::Short)],
+            "stray release and repeated press do nothing"
+        );
+    }
+
+    #[test]
+    fn names() {
+        for gesture in Gesture::ALL {
+            assert_eq!(
+                gesture.name().parse(),
+                Ok(gesture),
+                "name of {gesture:?} parses back"
+            );
+        }
+        for action in ButtonAction::ALL {
+            assert_eq!(
+                action.name().parse(),
+                Ok(action),
+                "name of {action:?} parses back"
+            );
+        }
+    }
+}
@@ -0,0 +1,6 @@
+//! # Buzzer
+//! Patterns, melodies and button gestures of the buzzer.
+
+pub mod gesture;
+pub mod pattern;
+pub mod rtttl;
@@ -0,0 +1,165 @@
+//! # Beep Patterns
+//! Named patterns the buzzer can play instead of a continuous tone.
+//!
+//! A pattern is either a sequence of [`Step`]s or an [RTTTL](super::rtttl) melody,
+//! which is repeated until the buzzer is stopped. The duty of each step is
+//! relative to the set volume, so patterns still respect the volume.
+
+use core::str::FromStr;
+
+use super::rtttl::Note;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// A single step of a [`Pattern`].
+pub struct Step {
+    /// Duty as a percentage of the set volume. Silent if 0.
+    pub duty: u8,
+    /// How long the step lasts in milliseconds.
+    pub ms: u32,
+    /// Frequency of the tone in Hz for a passive buzzer.
+    /// Uses the base PWM frequency if `None`.
+    pub hz: Option<u32>,
+}
+
+impl From<Note> for Step {
+    #[inline]
+    fn from(note: Note) -> Self {
+        Self {
+            duty: if note.frequency.is_some() { 100 } else { 0 },
+            ms: note.ms,
+            hz: note.frequency,
+        }
+    }
+}
+
+#[inline]
+const fn on(ms: u32) -> Step {
+    Step {
+        duty: 100,
+        ms,
+        hz: None,
+    }
+}
+
+#[inline]
+const fn off(ms: u32) -> Step {
+    Step {
+        duty: 0,
+        ms,
+        hz: None,
+    }
+}
+
+const CONTINUOUS: &[Step] = &[on(1000)];
+const TRIPLE_BEEP: &[Step] = &[on(150), off(100), on(150), off(100), on(150), off(800)];
+const PULSE: &[Step] = &[
+    on(500),
+    Step {
+        duty: 30,
+        ms: 500,
+        hz: None,
+    },
+];
+#[rustfmt::skip]
+const SOS: &[Step] = &[
+    on(150), off(150), on(150), off(150), on(150), off(450),
+    on(450), off(150), on(450), off(150), on(450), off(450),
+    on(150), off(150), on(150), off(150), on(150), off(1050),
+];
+
+const WESTMINSTER: &str = "Westminster:d=4,o=5,b=80:e,g#,f#,2b4,e,f#,g#,2e,g#,e,f#,2b4,b4,f#,g#,2e";
+const ODE_TO_JOY: &str = "Ode to Joy:d=4,o=5,b=120:e,e,f,g,g,f,e,d,c,c,d,e,e.,8d,2d,\
+    e,e,f,g,g,f,e,d,c,c,d,e,d.,8c,2c";
+
+/// Silence between repeats of a melody.
+pub const MELODY_GAP: Step = off(1000);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Pattern {
+    Continuous,
+    TripleBeep,
+    Pulse,
+    Sos,
+    Westminster,
+    OdeToJoy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[error("Unknown pattern")]
+pub struct UnknownPattern;
+
+/// What a [`Pattern`] is made of.
+pub enum Sound {
+    Steps(&'static [Step]),
+    /// An RTTTL melody. Requires a passive buzzer.
+    Melody(&'static str),
+}
+
+impl Pattern {
+    pub const ALL: [Self; 6] = [
+        Self::Continuous,
+        Self::TripleBeep,
+        Self::Pulse,
+        Self::Sos,
+        Self::Westminster,
+        Self::OdeToJoy,
+    ];
+
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Continuous => "continuous",
+            Self::TripleBeep => "triple-beep",
+            Self::Pulse => "pulse",
+            Self::Sos => "sos",
+            Self::Westminster => "westminster",
+            Self::OdeToJoy => "ode-to-joy",
+        }
+    }
+
+    #[inline]
+    pub const fn sound(self) -> Sound {
+        match self {
+            Self::Continuous => Sound::Steps(CONTINUOUS),
+            Self::TripleBeep => Sound::Steps(TRIPLE_BEEP),
+            Self::Pulse => Sound::Steps(PULSE),
+            Self::Sos => Sound::Steps(SOS),
+            Self::Westminster => Sound::Melody(WESTMINSTER),
+            Self::OdeToJoy => Sound::Melody(ODE_TO_JOY),
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = UnknownPattern;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|p| p.name().eq_ignore_ascii_case(s))
+            .ok_or(UnknownPattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buzzer::rtttl::Melody;
+
+    #[test]
+    fn melodies_are_valid() {
+        for pattern in Pattern::ALL {
+            if let Sound::Melody(rtttl) = pattern.sound() {
+                let melody = Melody::parse(rtttl);
+                assert!(melody.is_ok(), "{} header: {melody:?}", pattern.name());
+
+                for note in melody.unwrap() {
+                    assert!(note.is_ok(), "{} note: {note:?}", pattern.name());
+                }
+            }
+        }
+    }
+}
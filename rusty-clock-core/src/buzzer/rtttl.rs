@@ -0,0 +1,338 @@
+//! # RTTTL
+//! A parser for RTTTL (Ring Tone Text Transfer Language) melodies.
+//!
+//! A melody consists of a name, defaults and notes separated by colons,
+//! e.g. `Scale:d=4,o=5,b=120:c,d,e,f,g,a,b,c6`.
+//!
+//! Each note is written as `[duration]<note>[#][.][octave][.]`, where
+//! `p` is a pause. Missing durations and octaves use the defaults.
+
+use core::str::Split;
+
+/// Frequencies of the 4th octave in centihertz, starting from C.
+const OCTAVE4_CENTIHZ: [u32; 12] = [
+    26163, 27718, 29366, 31113, 32963, 34923, 36999, 39200, 41530, 44000, 46616, 49388,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RtttlError {
+    #[error("Melody must have a name, defaults and notes separated by `:`")]
+    MissingSection,
+    #[error("Invalid default value")]
+    InvalidDefault,
+    #[error("Invalid note")]
+    InvalidNote,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Note {
+    /// Frequency in Hz. `None` if the note is a pause.
+    pub frequency: Option<u32>,
+    /// How long the note lasts in milliseconds.
+    pub ms: u32,
+}
+
+#[derive(Debug, Clone)]
+/// A parsed RTTTL melody. Iterates over its notes.
+pub struct Melody<'a> {
+    pub name: &'a str,
+    /// Default duration as a fraction of a whole note.
+    duration: u32,
+    /// Default octave.
+    octave: u32,
+    /// Beats (quarter notes) per minute.
+    bpm: u32,
+    notes: Split<'a, char>,
+}
+
+impl<'a> Melody<'a> {
+    /// Parses the name and defaults of a melody.
+    ///
+    /// Notes are parsed lazily while iterating.
+    ///
+    /// # Errors
+    /// Returns an error if the sections or defaults are invalid.
+    pub fn parse(s: &'a str) -> Result<Self, RtttlError> {
+        let mut sections = s.splitn(3, ':');
+        let (Some(name), Some(defaults), Some(notes)) =
+            (sections.next(), sections.next(), sections.next())
+        else {
+            return Err(RtttlError::MissingSection);
+        };
+
+        // Defaults as defined by the spec
+        let mut melody = Self {
+            name: name.trim(),
+            duration: 4,
+            octave: 6,
+            bpm: 63,
+            notes: notes.split(','),
+        };
+
+        for default in defaults.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (key, value) = default.split_once('=').ok_or(RtttlError::InvalidDefault)?;
+            let value = value
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| RtttlError::InvalidDefault)?;
+
+            match key.trim() {
+                "d" if is_valid_duration(value) => melody.duration = value,
+                "o" if is_valid_octave(value) => melody.octave = value,
+                "b" if (1..=900).contains(&value) => melody.bpm = value,
+                _ => return Err(RtttlError::InvalidDefault),
+            }
+        }
+
+        Ok(melody)
+    }
+
+    /// Parses a single note.
+    fn parse_note(&self, s: &str) -> Result<Note, RtttlError> {
+        let mut chars = s.chars().peekable();
+
+        let mut duration: Option<u32> = None;
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            let digit = digit.to_digit(10).ok_or(RtttlError::InvalidNote)?;
+            duration = duration
+                .unwrap_or(0)
+                .checked_mul(10)
+                .and_then(|d| d.checked_add(digit));
+        }
+        let duration = duration.unwrap_or(self.duration);
+        if !is_valid_duration(duration) {
+            return Err(RtttlError::InvalidNote);
+        }
+
+        let semitone = match chars.next().map(|c| c.to_ascii_lowercase()) {
+            Some('c') => Some(0),
+            Some('d') => Some(2),
+            Some('e') => Some(4),
+            Some('f') => Some(5),
+            Some('g') => Some(7),
+            Some('a') => Some(9),
+            Some('b' | 'h') => Some(11),
+            Some('p') => None,
+            _ => return Err(RtttlError::InvalidNote),
+        };
+
+        let sharp = chars.next_if_eq(&'#').is_some();
+        let mut dotted = chars.next_if_eq(&'.').is_some();
+        let octave = match chars.next_if(char::is_ascii_digit) {
+            Some(digit) => digit.to_digit(10).ok_or(RtttlError::InvalidNote)?,
+            None => self.octave,
+        };
+        dotted |= chars.next_if_eq(&'.').is_some();
+
+        if chars.next().is_some() || !is_valid_octave(octave) {
+            return Err(RtttlError::InvalidNote);
+        }
+
+        // A whole note lasts 4 beats
+        let ms = 240_000_u32
+            .checked_div(self.bpm.saturating_mul(duration))
+            .ok_or(RtttlError::InvalidNote)?;
+        let ms = if dotted {
+            ms.saturating_add(ms.div_euclid(2))
+        } else {
+            ms
+        };
+
+        let frequency = semitone.map(|semitone: u32| {
+            let semitone = if sharp {
+                semitone.saturating_add(1)
+            } else {
+                semitone
+            };
+            frequency(semitone, octave)
+        });
+
+        Ok(Note { frequency, ms })
+    }
+}
+
+impl Iterator for Melody<'_> {
+    type Item = Result<Note, RtttlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let note = self.notes.next()?.trim();
+            if !note.is_empty() {
+                return Some(self.parse_note(note));
+            }
+        }
+    }
+}
+
+#[inline]
+fn is_valid_duration(duration: u32) -> bool {
+    matches!(duration, 1 | 2 | 4 | 8 | 16 | 32)
+}
+
+#[inline]
+fn is_valid_octave(octave: u32) -> bool {
+    (3..=8).contains(&octave)
+}
+
+/// Returns the frequency of a note in Hz, rounded to the nearest integer.
+///
+/// `semitone` is the number of semitones above C, and may be 12 for B#.
+fn frequency(semitone: u32, octave: u32) -> u32 {
+    let (semitone, octave) = if semitone >= 12 {
+        (semitone.saturating_sub(12), octave.saturating_add(1))
+    } else {
+        (semitone, octave)
+    };
+
+    let centihz = OCTAVE4_CENTIHZ
+        .get(usize::try_from(semitone).unwrap_or(usize::MAX))
+        .copied()
+        .unwrap_or_default();
+
+    let centihz = if octave >= 4 {
+        centihz.wrapping_shl(octave.saturating_sub(4))
+    } else {
+        centihz.wrapping_shr(4_u32.saturating_sub(octave))
+    };
+
+    centihz.saturating_add(50).div_euclid(100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes(s: &str) -> Result<heapless::Vec<Note, 32>, RtttlError> {
+        Melody::parse(s)?.collect()
+    }
+
+    #[test]
+    fn header() {
+        let melody = Melody::parse("Test Tune:d=8,o=5,b=120:c").unwrap();
+        assert_eq!(melody.name, "Test Tune", "name is trimmed");
+        assert_eq!(
+            (melody.duration, melody.octave, melody.bpm),
+            (8, 5, 120),
+            "defaults are read"
+        );
+    }
+
+    #[test]
+    fn spec_defaults() {
+        let melody = Melody::parse("Empty::c").unwrap();
+        assert_eq!(
+            (melody.duration, melody.octave, melody.bpm),
+            (4, 6, 63),
+            "spec defaults are used when missing"
+        );
+
+        let melody = Melody::parse("Spaced: b = 100 , d=2 :c").unwrap();
+        assert_eq!(
+            (melody.duration, melody.bpm),
+            (2, 100),
+            "whitespace is ignored"
+        );
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert_eq!(
+            Melody::parse("NoNotes:d=4").unwrap_err(),
+            RtttlError::MissingSection,
+            "notes section is required"
+        );
+        assert_eq!(
+            Melody::parse("X:d=3:c").unwrap_err(),
+            RtttlError::InvalidDefault,
+            "3 is not a valid duration"
+        );
+        assert_eq!(
+            Melody::parse("X:o=9:c").unwrap_err(),
+            RtttlError::InvalidDefault,
+            "octave is out of range"
+        );
+        assert_eq!(
+            Melody::parse("X:b=0:c").unwrap_err(),
+            RtttlError::InvalidDefault,
+            "zero bpm"
+        );
+        assert_eq!(
+            Melody::parse("X:x=4:c").unwrap_err(),
+            RtttlError::InvalidDefault,
+            "unknown key"
+        );
+    }
+
+    #[test]
+    fn frequencies() {
+        let notes = notes("X:d=4,o=4,b=60:a,a5,c,c#,b#,h,e3").unwrap();
+        let hz: heapless::Vec<_, 8> = notes.iter().map(|n| n.frequency).collect();
+
+        assert_eq!(
+            hz.as_slice(),
+            &[
+                Some(440),
+                Some(880),
+                Some(262),
+                Some(277),
+                Some(523),
+                Some(494),
+                Some(165)
+            ],
+            "A4, A5, C4, C#4, B#4 = C5, H4 = B4, E3"
+        );
+    }
+
+    #[test]
+    fn durations() {
+        let notes = notes("X:d=4,o=5,b=120:c,8c,2c.,16p,1c,4c6.").unwrap();
+        let ms: heapless::Vec<_, 8> = notes.iter().map(|n| n.ms).collect();
+
+        assert_eq!(
+            ms.as_slice(),
+            &[500, 250, 1500, 125, 2000, 750],
+            "quarter note is a beat and dots add half"
+        );
+    }
+
+    #[test]
+    fn pause() {
+        let notes = notes("X:d=4,o=5,b=60:p,8P").unwrap();
+        assert_eq!(
+            notes.as_slice(),
+            &[
+                Note {
+                    frequency: None,
+                    ms: 1000
+                },
+                Note {
+                    frequency: None,
+                    ms: 500
+                }
+            ],
+            "pauses have no frequency"
+        );
+    }
+
+    #[test]
+    fn whitespace_and_trailing_commas() {
+        let notes = notes("X:d=4,o=5,b=60: c , d ,\n e,").unwrap();
+        assert_eq!(notes.len(), 3, "empty notes are skipped");
+    }
+
+    #[test]
+    fn invalid_notes() {
+        for note in ["x", "3c", "c9", "c#x", "64c", "c..5"] {
+            let s: heapless::String<32> = heapless::format!("X:d=4,o=5,b=60:{note}").unwrap();
+            let result = Melody::parse(&s).unwrap().next();
+
+            assert_eq!(
+                result,
+                Some(Err(RtttlError::InvalidNote)),
+                "{note:?} is invalid"
+            );
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! # Rusty-Clock Core
+//! The logic of Rusty-Clock that does not touch the hardware.
+//!
+//! Unlike the firmware, this crate also builds for the host, so its tests can run.
+//! See the `test-core` recipe in the Justfile.
+
+#![no_std]
+#![feature(const_convert, const_trait_impl, integer_widen_truncate)]
+// Clippy Lints
+#![deny(
+    clippy::indexing_slicing,
+    reason = "Prefer `.get()` unless absolutely sure index cannot be out of bounds."
+)]
+#![deny(
+    clippy::as_conversions,
+    reason = "`as` conversions are not explicit enough."
+)]
+#![deny(
+    clippy::integer_division,
+    reason = "Integer divison discards the remainder"
+)]
+#![deny(
+    clippy::get_unwrap,
+    clippy::lossy_float_literal,
+    clippy::cast_lossless,
+    clippy::cast_possible_truncation,
+    clippy::cast_possible_wrap,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss,
+    clippy::arithmetic_side_effects,
+    clippy::string_slice,
+    clippy::cfg_not_test,
+    clippy::ref_patterns
+)]
+#![warn(
+    clippy::allow_attributes_without_reason,
+    reason = "All `allow/expect` macros should be documented"
+)]
+#![warn(
+    clippy::pedantic,
+    clippy::unused_trait_names,
+    clippy::semicolon_if_nothing_returned,
+    clippy::if_then_some_else_none,
+    clippy::missing_assert_message
+)]
+#![allow(
+    clippy::must_use_candidate,
+    reason = "Only the firmware calls into this crate"
+)]
+
+pub mod buzzer;
+pub mod ntp;
+pub mod rtc;
@@ -0,0 +1,143 @@
+//! # Kiss-o'-Death
+//! Servers send a kiss-o'-death instead of the time to tell a client to back off
+//! (RFC 5905 §7.4). DENY and RSTR mean the server must not be used again,
+//! and RATE means it is asked too often.
+
+use embassy_time::{Duration, Instant};
+use sntpc::KissOfDeathCode;
+
+/// How long a server that sent RATE is left alone. Doubles with each RATE in a row.
+const MIN_RATE_HOLDOFF: Duration = Duration::from_secs(60 * 60);
+
+/// The longest a server that sent RATE is left alone.
+const MAX_RATE_HOLDOFF: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Whether an NTP server may be asked for the time.
+pub enum ServerState {
+    Usable,
+    /// Sent DENY or RSTR, so it is not used again until reboot.
+    Denied,
+    /// Sent RATE, so it is not used until `until`.
+    RateLimited {
+        until: Instant,
+        /// How long it is left alone for.
+        holdoff: Duration,
+    },
+}
+
+impl ServerState {
+    /// Whether the server may be sent requests at `now`.
+    #[inline]
+    pub fn is_usable(&self, now: Instant) -> bool {
+        match self {
+            Self::Usable => true,
+            Self::Denied => false,
+            Self::RateLimited { until, .. } => now >= *until,
+        }
+    }
+
+    /// Backs off after the server sent a kiss-o'-death with `code` at `now`.
+    pub fn kiss(&mut self, code: KissOfDeathCode, now: Instant) {
+        if let Self::Denied = self {
+            return;
+        }
+
+        match code {
+            KissOfDeathCode::Deny | KissOfDeathCode::Rstr => *self = Self::Denied,
+            KissOfDeathCode::Rate => {
+                let holdoff = match *self {
+                    Self::RateLimited { holdoff, .. } => {
+                        Duration::from_ticks(holdoff.as_ticks().saturating_mul(2))
+                            .min(MAX_RATE_HOLDOFF)
+                    }
+                    Self::Usable | Self::Denied => MIN_RATE_HOLDOFF,
+                };
+
+                *self = Self::RateLimited {
+                    until: now.checked_add(holdoff).unwrap_or(Instant::MAX),
+                    holdoff,
+                };
+            }
+            // Other codes mean nothing to a client
+            _ => {}
+        }
+    }
+
+    /// Resets the back off after the server answered with the time.
+    #[inline]
+    pub fn answered(&mut self) {
+        if let Self::RateLimited { .. } = self {
+            *self = Self::Usable;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deny_is_permanent() {
+        for code in [KissOfDeathCode::Deny, KissOfDeathCode::Rstr] {
+            let mut state = ServerState::Usable;
+            state.kiss(code, Instant::from_secs(0));
+            assert_eq!(state, ServerState::Denied, "{code:?}");
+            assert!(!state.is_usable(Instant::MAX), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn rate_backs_off() {
+        let mut state = ServerState::Usable;
+        state.kiss(KissOfDeathCode::Rate, Instant::from_secs(0));
+        assert!(!state.is_usable(Instant::from_secs(60)), "held off");
+        assert!(state.is_usable(Instant::from_secs(3_600)), "after an hour");
+
+        state.kiss(KissOfDeathCode::Rate, Instant::from_secs(3_600));
+        assert!(
+            !state.is_usable(Instant::from_secs(2 * 3_600)),
+            "doubled when sent again"
+        );
+        assert!(
+            state.is_usable(Instant::from_secs(3 * 3_600)),
+            "after two hours"
+        );
+
+        state.answered();
+        assert_eq!(state, ServerState::Usable, "reset by an answer");
+    }
+
+    #[test]
+    fn rate_backoff_is_capped() {
+        let mut state = ServerState::Usable;
+        for _ in 0..20 {
+            state.kiss(KissOfDeathCode::Rate, Instant::from_secs(0));
+        }
+        assert_eq!(
+            state,
+            ServerState::RateLimited {
+                until: Instant::from_secs(7 * 24 * 3_600),
+                holdoff: MAX_RATE_HOLDOFF,
+            },
+            "capped at a week"
+        );
+    }
+
+    #[test]
+    fn other_codes_are_ignored() {
+        let mut state = ServerState::Usable;
+        state.kiss(KissOfDeathCode::Init, Instant::from_secs(0));
+        state.kiss(
+            KissOfDeathCode::Experimental(*b"XTST"),
+            Instant::from_secs(0),
+        );
+        assert_eq!(state, ServerState::Usable);
+
+        let mut state = ServerState::Denied;
+        state.kiss(KissOfDeathCode::Rate, Instant::from_secs(0));
+        state.answered();
+        assert_eq!(state, ServerState::Denied, "a deny is never undone");
+    }
+}
@@ -0,0 +1,7 @@
+//! # NTP
+//! Packets and server selection of the SNTP client and server.
+
+pub mod kiss;
+pub mod packet;
+pub mod sample;
+pub mod server;
@@ -0,0 +1,331 @@
+//! # NTP Packets
+//! Builds SNTP version 4 responses (RFC 4330) from the RTC time.
+
+use core::net::IpAddr;
+
+/// Length of an NTP packet without extension fields.
+pub const NTP_PACKET_LEN: usize = 48;
+
+/// Seconds from the NTP epoch, 1900-01-01, to the Unix epoch.
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+const MAX_VERSION: u8 = 4;
+
+/// Leap indicator of a clock that is not synchronised.
+const LI_UNSYNCHRONISED: u8 = 3;
+/// Stratum of a clock that is not synchronised.
+const STRATUM_UNSYNCHRONISED: u8 = 16;
+/// Stratum of a time that was not synced since boot, same as an undisciplined local clock in ntpd.
+const STRATUM_LOCAL: u8 = 10;
+const MAX_STRATUM: u8 = 15;
+
+/// The RTC time is served in whole seconds, so it is
+/// only good to about a second. In log2 seconds.
+const PRECISION: i8 = 0;
+/// Dispersion of the served time itself.
+const BASE_DISPERSION_US: u64 = 1_000_000;
+/// Dispersion grows by the DS3231's worst case drift since the last sync.
+const DRIFT_PPM: u64 = 2;
+/// Clients reject a dispersion of 16 seconds and up.
+const MAX_DISPERSION_US: u64 = 15_000_000;
+
+/// Where the served time comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The RTC lost its time or was never set.
+    Unsynchronised,
+    /// The RTC holds a time that was not synced with NTP since boot.
+    Local,
+    /// The RTC was synced with an NTP server.
+    Upstream {
+        server: IpAddr,
+        /// Stratum of the server.
+        stratum: u8,
+        /// Unix timestamp of the sync.
+        synced_at: i64,
+        /// Error the RTC was set with, in us.
+        error_us: u64,
+    },
+}
+
+/// Builds the response to an SNTP request, where `now` is the current Unix timestamp.
+///
+/// Returns [`None`] if the request is not a client request.
+pub fn respond(request: &[u8], now: i64, source: Source) -> Option<[u8; NTP_PACKET_LEN]> {
+    let request = request.get(..NTP_PACKET_LEN)?;
+    let li_vn_mode = *request.first()?;
+    let version = li_vn_mode.wrapping_shr(3) & 0b111;
+    let mode = li_vn_mode & 0b111;
+    if mode != MODE_CLIENT || !(1..=MAX_VERSION).contains(&version) {
+        return None;
+    }
+
+    let (leap, stratum, reference_id, reference_ts, dispersion_us) = match source {
+        Source::Unsynchronised => (
+            LI_UNSYNCHRONISED,
+            STRATUM_UNSYNCHRONISED,
+            *b"INIT",
+            None,
+            BASE_DISPERSION_US,
+        ),
+        Source::Local => (0, STRATUM_LOCAL, *b"LOCL", None, BASE_DISPERSION_US),
+        Source::Upstream {
+            server,
+            stratum,
+            synced_at,
+            error_us,
+        } => {
+            let elapsed = now.saturating_sub(synced_at).max(0).cast_unsigned();
+            let dispersion_us = BASE_DISPERSION_US
+                .saturating_add(error_us)
+                .saturating_add(elapsed.saturating_mul(DRIFT_PPM));
+
+            // Stratum 2 and up are identified by the server's IPv4 address
+            let reference_id = match server {
+                IpAddr::V4(addr) => addr.octets(),
+                IpAddr::V6(_) => [0; 4],
+            };
+
+            (
+                0,
+                stratum.saturating_add(1).min(MAX_STRATUM),
+                reference_id,
+                Some(synced_at),
+                dispersion_us,
+            )
+        }
+    };
+
+    let mut response = heapless::Vec::<u8, NTP_PACKET_LEN>::new();
+    let poll = request.get(2).copied().unwrap_or(0);
+    response
+        .extend_from_slice(&[
+            leap.wrapping_shl(6) | version.wrapping_shl(3) | MODE_SERVER,
+            stratum,
+            poll,
+            PRECISION.cast_unsigned(),
+        ])
+        .ok()?;
+    // Root delay is not known
+    response.extend_from_slice(&[0; 4]).ok()?;
+    response
+        .extend_from_slice(&short_format(dispersion_us.min(MAX_DISPERSION_US)))
+        .ok()?;
+    response.extend_from_slice(&reference_id).ok()?;
+    response
+        .extend_from_slice(&reference_ts.map_or([0; 8], timestamp))
+        .ok()?;
+    // Origin timestamp is the transmit timestamp of the request
+    response.extend_from_slice(request.get(40..)?).ok()?;
+    // Receive and transmit timestamps
+    response.extend_from_slice(&timestamp(now)).ok()?;
+    response.extend_from_slice(&timestamp(now)).ok()?;
+
+    response.into_array().ok()
+}
+
+/// Encodes a Unix timestamp as an NTP timestamp. Wraps around in 2036 like NTP does.
+fn timestamp(unix: i64) -> [u8; 8] {
+    let secs = unix
+        .saturating_add(NTP_UNIX_OFFSET)
+        .rem_euclid(1 << 32)
+        .cast_unsigned();
+    // The fraction is always 0 since the time is in whole seconds
+    (secs.wrapping_shl(32)).to_be_bytes()
+}
+
+/// Encodes microseconds in the NTP short format, 16 bits of seconds and 16 of fraction.
+fn short_format(us: u64) -> [u8; 4] {
+    let short = us.saturating_mul(1 << 16).div_euclid(1_000_000);
+    u32::try_from(short).unwrap_or(u32::MAX).to_be_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{
+        cell::RefCell,
+        net::{Ipv4Addr, SocketAddr},
+    };
+    use sntpc::{NtpContext, NtpResult, NtpTimestampGenerator, NtpUdpSocket};
+
+    const NOW: i64 = 1_767_225_600;
+    const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 123);
+
+    /// Answers requests with [`respond`] instead of sending them anywhere.
+    struct Loopback {
+        source: Source,
+        response: RefCell<Option<[u8; NTP_PACKET_LEN]>>,
+    }
+
+    #[expect(clippy::unused_async_trait_impl, reason = "Answers right away")]
+    impl NtpUdpSocket for Loopback {
+        async fn send_to(&self, buf: &[u8], _addr: SocketAddr) -> sntpc::Result<usize> {
+            *self.response.borrow_mut() = respond(buf, NOW, self.source);
+            Ok(buf.len())
+        }
+
+        async fn recv_from(&self, buf: &mut [u8]) -> sntpc::Result<(usize, SocketAddr)> {
+            let response = self
+                .response
+                .borrow_mut()
+                .take()
+                .ok_or(sntpc::Error::Network)?;
+            buf.get_mut(..NTP_PACKET_LEN)
+                .ok_or(sntpc::Error::IncorrectPayload)?
+                .copy_from_slice(&response);
+            Ok((NTP_PACKET_LEN, SERVER))
+        }
+    }
+
+    /// The client's clock, which agrees with the server.
+    #[derive(Clone, Copy)]
+    struct ClientClock;
+
+    impl NtpTimestampGenerator for ClientClock {
+        fn init(&mut self) {}
+
+        fn timestamp_sec(&self) -> u64 {
+            NOW.cast_unsigned()
+        }
+
+        fn timestamp_subsec_micros(&self) -> u32 {
+            0
+        }
+    }
+
+    fn get_time(source: Source) -> sntpc::Result<NtpResult> {
+        let socket = Loopback {
+            source,
+            response: RefCell::new(None),
+        };
+        embassy_futures::block_on(sntpc::get_time(
+            SERVER,
+            &socket,
+            NtpContext::new(ClientClock),
+        ))
+    }
+
+    fn client_request() -> [u8; NTP_PACKET_LEN] {
+        let mut request = [0; NTP_PACKET_LEN];
+        // LI 0, version 4, client mode
+        request.fill(0x11);
+        if let Some(first) = request.first_mut() {
+            *first = 0x23;
+        }
+        request
+    }
+
+    #[test]
+    fn upstream() {
+        let result = get_time(Source::Upstream {
+            server: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+            stratum: 2,
+            synced_at: NOW - 3_600,
+            error_us: 5_000,
+        })
+        .expect("synced time is accepted");
+
+        assert_eq!(result.seconds, NOW.cast_unsigned(), "{result:?}");
+        assert_eq!(result.offset, 0, "{result:?}");
+        assert_eq!(result.stratum, 3, "one below upstream");
+        assert_eq!(result.leap_indicator, 0, "synchronised");
+        assert_eq!(result.reference_id, [192, 0, 2, 1], "upstream address");
+        // 1s base + 5ms error + 2ppm over an hour is 1.0122s
+        assert_eq!(result.root_dispersion, 66_335, "{result:?}");
+    }
+
+    #[test]
+    fn upstream_stratum_is_capped() {
+        let result = get_time(Source::Upstream {
+            server: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+            stratum: 15,
+            synced_at: NOW,
+            error_us: 0,
+        })
+        .expect("synced time is accepted");
+        assert_eq!(result.stratum, 15, "16 would mean unsynchronised");
+    }
+
+    #[test]
+    fn dispersion_is_capped() {
+        let result = get_time(Source::Upstream {
+            server: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+            stratum: 1,
+            // A year ago
+            synced_at: NOW - 365 * 24 * 3_600,
+            error_us: 0,
+        })
+        .expect("old sync is still accepted");
+        assert_eq!(result.root_dispersion, 15 << 16, "capped at 15s");
+    }
+
+    #[test]
+    fn local() {
+        let result = get_time(Source::Local).expect("local time is accepted");
+        assert_eq!(result.seconds, NOW.cast_unsigned(), "{result:?}");
+        assert_eq!(result.stratum, STRATUM_LOCAL, "local clock");
+        assert_eq!(result.reference_id, *b"LOCL", "local clock");
+    }
+
+    #[test]
+    fn unsynchronised() {
+        let result = get_time(Source::Unsynchronised);
+        assert!(
+            matches!(result, Err(sntpc::Error::UnsynchronizedClock)),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn origin_is_copied() {
+        let mut request = client_request();
+        if let Some(transmit) = request.get_mut(40..) {
+            transmit.copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        }
+
+        let response = respond(&request, NOW, Source::Local).expect("response");
+        assert_eq!(
+            response.get(24..32),
+            Some([1, 2, 3, 4, 5, 6, 7, 8].as_slice()),
+            "origin timestamp"
+        );
+        assert_eq!(response.get(2), Some(&0x11), "poll is copied");
+    }
+
+    #[test]
+    fn version_is_echoed() {
+        let mut request = client_request();
+        // Version 3
+        if let Some(first) = request.first_mut() {
+            *first = 0x1B;
+        }
+
+        let response = respond(&request, NOW, Source::Local).expect("response");
+        assert_eq!(response.first(), Some(&0x1C), "version 3, server mode");
+    }
+
+    #[test]
+    fn ignores_other_packets() {
+        let mut request = client_request();
+        assert_eq!(
+            respond(request.get(..47).unwrap_or(&[]), NOW, Source::Local),
+            None,
+            "too short"
+        );
+
+        // Server mode
+        if let Some(first) = request.first_mut() {
+            *first = 0x24;
+        }
+        assert_eq!(respond(&request, NOW, Source::Local), None, "not a request");
+
+        // Version 0
+        if let Some(first) = request.first_mut() {
+            *first = 0x03;
+        }
+        assert_eq!(respond(&request, NOW, Source::Local), None, "bad version");
+    }
+}
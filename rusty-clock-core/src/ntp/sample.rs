@@ -0,0 +1,130 @@
+//! # NTP Samples
+//! Picks the best of several NTP responses.
+//!
+//! Each [`Sample`] holds the offset between the server's clock and [`Instant`](embassy_time::Instant),
+//! so samples taken at different times can be compared. Samples whose offset is far from the
+//! median are outliers, e.g. a server with the wrong time. Of the rest, the one with the lowest
+//! round-trip delay is the most accurate, since the delay is the uncertainty of the offset.
+
+use core::net::IpAddr;
+
+/// Samples further than this from the median offset are outliers.
+const MAX_OFFSET_SPREAD_US: u64 = 250_000;
+
+/// Samples slower than this are too uncertain to use.
+const MAX_ROUNDTRIP_US: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sample {
+    /// Server time minus [`Instant::now`](embassy_time::Instant::now) when it was received, in microseconds.
+    pub offset_us: i64,
+    /// Round-trip delay of the request in microseconds.
+    pub roundtrip_us: u64,
+    /// The server that sent the sample.
+    pub server: IpAddr,
+    /// Stratum of the server.
+    pub stratum: u8,
+}
+
+/// Returns the sample with the lowest round-trip delay that agrees with the majority.
+///
+/// Returns [`None`] if there are no fast enough samples, or no majority agrees on the offset.
+pub fn select(samples: &mut [Sample]) -> Option<Sample> {
+    // Slow samples go last, so the rest are sorted by offset at the front
+    samples.sort_unstable_by_key(|s| (s.roundtrip_us > MAX_ROUNDTRIP_US, s.offset_us));
+    let fast = samples
+        .iter()
+        .take_while(|s| s.roundtrip_us <= MAX_ROUNDTRIP_US)
+        .count();
+    let samples = samples.get(..fast)?;
+
+    let median = samples.get(fast.div_euclid(2))?.offset_us;
+    let is_inlier = |s: &&Sample| s.offset_us.abs_diff(median) <= MAX_OFFSET_SPREAD_US;
+
+    let inliers = samples.iter().filter(is_inlier).count();
+    if inliers.saturating_mul(2) <= fast {
+        return None;
+    }
+
+    samples
+        .iter()
+        .filter(is_inlier)
+        .min_by_key(|s| s.roundtrip_us)
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::net::Ipv4Addr;
+
+    const fn sample(offset_us: i64, roundtrip_us: u64) -> Sample {
+        Sample {
+            offset_us,
+            roundtrip_us,
+            server: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            stratum: 2,
+        }
+    }
+
+    #[test]
+    fn lowest_roundtrip() {
+        let mut samples = [
+            sample(1_000_000, 40_000),
+            sample(1_010_000, 12_000),
+            sample(990_000, 30_000),
+        ];
+        assert_eq!(
+            select(&mut samples),
+            Some(sample(1_010_000, 12_000)),
+            "fastest sample wins"
+        );
+    }
+
+    #[test]
+    fn outliers_are_rejected() {
+        let mut samples = [
+            sample(1_000_000, 40_000),
+            // Wrong by an hour, but fastest
+            sample(3_601_000_000, 1_000),
+            sample(1_020_000, 30_000),
+        ];
+        assert_eq!(
+            select(&mut samples),
+            Some(sample(1_020_000, 30_000)),
+            "outlier is ignored even though it is fastest"
+        );
+    }
+
+    #[test]
+    fn slow_samples_are_rejected() {
+        let mut samples = [sample(1_000_000, 2_000_000), sample(1_000_000, 50_000)];
+        assert_eq!(
+            select(&mut samples),
+            Some(sample(1_000_000, 50_000)),
+            "slow sample is ignored"
+        );
+
+        let mut samples = [sample(1_000_000, 2_000_000)];
+        assert_eq!(select(&mut samples), None, "only sample is too slow");
+    }
+
+    #[test]
+    fn no_majority() {
+        let mut samples = [sample(0, 10_000), sample(60_000_000, 10_000)];
+        assert_eq!(select(&mut samples), None, "two servers disagree");
+
+        assert_eq!(select(&mut []), None, "no samples");
+    }
+
+    #[test]
+    fn single_sample() {
+        let mut samples = [sample(-5_000, 20_000)];
+        assert_eq!(
+            select(&mut samples),
+            Some(sample(-5_000, 20_000)),
+            "single sample is used"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! # SNTP Server
+//! Answers SNTP requests received on an [`NtpUdpSocket`] with [`respond`].
+
+use core::net::SocketAddr;
+
+use sntpc::NtpUdpSocket;
+
+use super::packet::{Source, respond};
+
+/// What was done with a received packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Served {
+    /// Answered the client request from the address.
+    Responded(SocketAddr),
+    /// The packet from the address was not a client request.
+    Ignored(SocketAddr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ServeError {
+    Receive(sntpc::Error),
+    Respond(sntpc::Error),
+}
+
+/// Receives a single packet on `socket` and answers it if it is a client request.
+///
+/// `clock` is only called once a packet arrived, and returns the current Unix timestamp
+/// along with where that time comes from. `buf` holds the request, so should be large
+/// enough for extension fields, which are ignored.
+///
+/// # Errors
+/// Returns an error if receiving the packet or sending the response failed.
+pub async fn serve_one(
+    socket: &impl NtpUdpSocket,
+    buf: &mut [u8],
+    clock: impl AsyncFnOnce() -> (i64, Source),
+) -> Result<Served, ServeError> {
+    let (len, from) = socket.recv_from(buf).await.map_err(ServeError::Receive)?;
+    let (now, source) = clock().await;
+
+    let Some(response) = buf
+        .get(..len)
+        .and_then(|request| respond(request, now, source))
+    else {
+        return Ok(Served::Ignored(from));
+    };
+
+    socket
+        .send_to(&response, from)
+        .await
+        .map_err(ServeError::Respond)?;
+    Ok(Served::Responded(from))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::{net::Ipv4Addr, time::Duration};
+    use sntpc::{NtpContext, NtpTimestampGenerator};
+    use std::{net::UdpSocket, thread, vec::Vec};
+
+    const NOW: i64 = 1_767_225_600;
+
+    /// A real UDP socket on the loopback interface.
+    struct Loopback(UdpSocket);
+
+    impl Loopback {
+        fn bind() -> Self {
+            let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).expect("bind loopback");
+            socket
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .expect("set timeout");
+            Self(socket)
+        }
+
+        fn addr(&self) -> SocketAddr {
+            self.0.local_addr().expect("bound address")
+        }
+    }
+
+    #[expect(
+        clippy::unused_async_trait_impl,
+        reason = "std sockets block instead of awaiting"
+    )]
+    impl NtpUdpSocket for Loopback {
+        async fn send_to(&self, buf: &[u8], addr: SocketAddr) -> sntpc::Result<usize> {
+            self.0.send_to(buf, addr).map_err(|_| sntpc::Error::Network)
+        }
+
+        async fn recv_from(&self, buf: &mut [u8]) -> sntpc::Result<(usize, SocketAddr)> {
+            self.0.recv_from(buf).map_err(|_| sntpc::Error::Network)
+        }
+    }
+
+    /// The client's clock, which agrees with the server.
+    #[derive(Clone, Copy)]
+    struct ClientClock;
+
+    impl NtpTimestampGenerator for ClientClock {
+        fn init(&mut self) {}
+
+        fn timestamp_sec(&self) -> u64 {
+            NOW.cast_unsigned()
+        }
+
+        fn timestamp_subsec_micros(&self) -> u32 {
+            0
+        }
+    }
+
+    /// Serves `packets` packets on a loopback socket in the background, like the server task.
+    ///
+    /// Returns the address of the server and what was done with each packet.
+    fn spawn_server(
+        packets: usize,
+        source: Source,
+    ) -> (
+        SocketAddr,
+        thread::JoinHandle<Vec<Result<Served, ServeError>>>,
+    ) {
+        let socket = Loopback::bind();
+        let addr = socket.addr();
+
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 128];
+            (0..packets)
+                .map(|_| {
+                    embassy_futures::block_on(serve_one(&socket, &mut buf, async || (NOW, source)))
+                })
+                .collect()
+        });
+
+        (addr, handle)
+    }
+
+    #[test]
+    fn answers_sntp_client() {
+        let (server, handle) = spawn_server(2, Source::Local);
+        let client = Loopback::bind();
+
+        for _ in 0..2 {
+            let result = embassy_futures::block_on(sntpc::get_time(
+                server,
+                &client,
+                NtpContext::new(ClientClock),
+            ))
+            .expect("served time is accepted");
+            assert_eq!(result.seconds, NOW.cast_unsigned(), "{result:?}");
+            assert_eq!(result.offset, 0, "{result:?}");
+            assert_eq!(result.reference_id, *b"LOCL", "{result:?}");
+        }
+
+        let outcomes = handle.join().expect("server thread");
+        assert_eq!(
+            outcomes,
+            [Ok(Served::Responded(client.addr())); 2],
+            "each request is answered"
+        );
+    }
+
+    #[test]
+    fn ignores_other_packets() {
+        let (server, handle) = spawn_server(2, Source::Local);
+        let client = Loopback::bind();
+
+        client
+            .0
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .expect("set timeout");
+        client.0.send_to(b"not ntp", server).expect("send");
+        let mut buf = [0; 128];
+        assert!(
+            client.0.recv_from(&mut buf).is_err(),
+            "no response to other packets"
+        );
+
+        embassy_futures::block_on(sntpc::get_time(
+            server,
+            &client,
+            NtpContext::new(ClientClock),
+        ))
+        .expect("still serves requests after");
+
+        let outcomes = handle.join().expect("server thread");
+        assert_eq!(
+            outcomes,
+            [
+                Ok(Served::Ignored(client.addr())),
+                Ok(Served::Responded(client.addr()))
+            ],
+            "only the request is answered"
+        );
+    }
+}
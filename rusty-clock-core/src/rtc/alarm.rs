@@ -0,0 +1,170 @@
+//! # Alarm Occurrences
+//! Finds when a DS3231 [`Alarm1Config`] went off last or goes off next.
+
+use chrono::{Datelike as _, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike as _};
+use ds3231::Alarm1Config;
+
+/// Converts a 12-hour clock hour to 24-hour if `is_pm` is set.
+#[inline]
+pub fn to_24_hour(hours: u8, is_pm: Option<bool>) -> u8 {
+    match is_pm {
+        None => hours,
+        Some(pm) => hours
+            .checked_rem(12)
+            .unwrap_or(0)
+            .saturating_add(if pm { 12 } else { 0 }),
+    }
+}
+
+/// Returns the most recent datetime, at or before `now`, at which `config` matches.
+///
+/// Both `now` and the returned datetime are in UTC, same as the DS3231.
+pub fn last_occurrence(config: &Alarm1Config, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let hms = |h: u8, m: u8, s: u8| NaiveTime::from_hms_opt(h.into(), m.into(), s.into());
+
+    match *config {
+        Alarm1Config::EverySecond => Some(now),
+        Alarm1Config::AtSeconds { seconds } => {
+            let dt = now.with_second(seconds.into())?;
+            if dt <= now {
+                Some(dt)
+            } else {
+                dt.checked_sub_signed(TimeDelta::minutes(1))
+            }
+        }
+        Alarm1Config::AtMinutesSeconds { minutes, seconds } => {
+            let dt = now
+                .with_minute(minutes.into())?
+                .with_second(seconds.into())?;
+            if dt <= now {
+                Some(dt)
+            } else {
+                dt.checked_sub_signed(TimeDelta::hours(1))
+            }
+        }
+        Alarm1Config::AtTime {
+            hours,
+            minutes,
+            seconds,
+            is_pm,
+        } => {
+            let time = hms(to_24_hour(hours, is_pm), minutes, seconds)?;
+            last_matching_day(now, time, 2, |_| true)
+        }
+        Alarm1Config::AtTimeOnDay {
+            hours,
+            minutes,
+            seconds,
+            day,
+            is_pm,
+        } => {
+            let time = hms(to_24_hour(hours, is_pm), minutes, seconds)?;
+            last_matching_day(now, time, 8, |date| {
+                date.weekday().number_from_sunday() == u32::from(day)
+            })
+        }
+        Alarm1Config::AtTimeOnDate {
+            hours,
+            minutes,
+            seconds,
+            date,
+            is_pm,
+        } => {
+            let time = hms(to_24_hour(hours, is_pm), minutes, seconds)?;
+            // Not every month has the 31st
+            last_matching_day(now, time, 62, |d| d.day() == u32::from(date))
+        }
+    }
+}
+
+/// Walks back up to `days` days from `now` and returns the first datetime
+/// at `time` that matches `predicate` and is not after `now`.
+fn last_matching_day(
+    now: NaiveDateTime,
+    time: NaiveTime,
+    days: u8,
+    predicate: impl Fn(NaiveDate) -> bool,
+) -> Option<NaiveDateTime> {
+    core::iter::successors(Some(now.date()), NaiveDate::pred_opt)
+        .take(days.into())
+        .filter(|date| predicate(*date))
+        .map(|date| date.and_time(time))
+        .find(|dt| *dt <= now)
+}
+
+/// Returns the earliest datetime, at or after `from`, at which `config` matches.
+///
+/// Unlike [`last_occurrence`], `from` and the returned datetime may be in any time zone,
+/// as long as both are in the same one.
+pub fn next_occurrence(config: &Alarm1Config, from: NaiveDateTime) -> Option<NaiveDateTime> {
+    let hms = |h: u8, m: u8, s: u8| NaiveTime::from_hms_opt(h.into(), m.into(), s.into());
+
+    match *config {
+        Alarm1Config::EverySecond => Some(from),
+        Alarm1Config::AtSeconds { seconds } => {
+            let dt = from.with_second(seconds.into())?;
+            if dt >= from {
+                Some(dt)
+            } else {
+                dt.checked_add_signed(TimeDelta::minutes(1))
+            }
+        }
+        Alarm1Config::AtMinutesSeconds { minutes, seconds } => {
+            let dt = from
+                .with_minute(minutes.into())?
+                .with_second(seconds.into())?;
+            if dt >= from {
+                Some(dt)
+            } else {
+                dt.checked_add_signed(TimeDelta::hours(1))
+            }
+        }
+        Alarm1Config::AtTime {
+            hours,
+            minutes,
+            seconds,
+            is_pm,
+        } => {
+            let time = hms(to_24_hour(hours, is_pm), minutes, seconds)?;
+            next_matching_day(from, time, 2, |_| true)
+        }
+        Alarm1Config::AtTimeOnDay {
+            hours,
+            minutes,
+            seconds,
+            day,
+            is_pm,
+        } => {
+            let time = hms(to_24_hour(hours, is_pm), minutes, seconds)?;
+            next_matching_day(from, time, 8, |date| {
+                date.weekday().number_from_sunday() == u32::from(day)
+            })
+        }
+        Alarm1Config::AtTimeOnDate {
+            hours,
+            minutes,
+            seconds,
+            date,
+            is_pm,
+        } => {
+            let time = hms(to_24_hour(hours, is_pm), minutes, seconds)?;
+            // Not every month has the 31st
+            next_matching_day(from, time, 62, |d| d.day() == u32::from(date))
+        }
+    }
+}
+
+/// Walks forward up to `days` days from `from` and returns the first datetime
+/// at `time` that matches `predicate` and is not before `from`.
+fn next_matching_day(
+    from: NaiveDateTime,
+    time: NaiveTime,
+    days: u8,
+    predicate: impl Fn(NaiveDate) -> bool,
+) -> Option<NaiveDateTime> {
+    core::iter::successors(Some(from.date()), NaiveDate::succ_opt)
+        .take(days.into())
+        .filter(|date| predicate(*date))
+        .map(|date| date.and_time(time))
+        .find(|dt| *dt >= from)
+}
@@ -0,0 +1,305 @@
+//! # RTC Drift
+//! Measures how fast the DS3231 runs compared to NTP, and works out
+//! the aging offset that trims it.
+//!
+//! Each NTP sync sets the RTC with a known error. At the next sync the error is
+//! measured again, and the change over the time in between is the drift.
+//!
+//! The aging offset register adds or removes capacitance from the oscillator.
+//! Each step is about 0.1 ppm at 25°C, and positive values slow the clock down.
+
+use heapless::Deque;
+
+/// The most drift samples that are kept. The oldest is dropped first.
+pub const MAX_DRIFT_SAMPLES: usize = 16;
+
+/// Shorter intervals are dominated by the error of the measurement itself.
+const MIN_SAMPLE_SECS: i64 = 60 * 60;
+
+/// How long samples with the current aging offset must cover before it is trimmed.
+const MIN_TRIM_SECS: i64 = 24 * 60 * 60;
+
+/// Drift corrected by one step of the aging offset, in parts per billion.
+const PPB_PER_STEP: i64 = 100;
+
+/// Far outside the DS3231's ±2 ppm, so the time must have been changed some other way.
+const MAX_DRIFT_PPB: i64 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DriftSample {
+    /// Unix timestamp of the measurement.
+    pub timestamp: i64,
+    /// Time since the RTC was set, in seconds.
+    pub elapsed_secs: i64,
+    /// Time the RTC gained since it was set, in us. Negative if it lost time.
+    pub gained_us: i64,
+    /// The aging offset the RTC ran with.
+    pub aging: i8,
+}
+
+impl DriftSample {
+    /// Drift in parts per billion. Positive if the RTC runs fast.
+    #[inline]
+    pub fn ppb(&self) -> i64 {
+        ppb(self.gained_us, self.elapsed_secs)
+    }
+}
+
+/// When the RTC was last set from NTP, and how far off it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SetPoint {
+    /// NTP time in us.
+    at_us: i64,
+    /// RTC minus NTP time in us.
+    error_us: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DriftLog {
+    samples: Deque<DriftSample, MAX_DRIFT_SAMPLES>,
+    last_set: Option<SetPoint>,
+    /// [`None`] until read from the RTC.
+    aging: Option<i8>,
+}
+
+impl DriftLog {
+    pub const fn new() -> Self {
+        Self {
+            samples: Deque::new(),
+            last_set: None,
+            aging: None,
+        }
+    }
+
+    /// The aging offset the RTC currently runs with.
+    #[inline]
+    pub const fn aging(&self) -> Option<i8> {
+        self.aging
+    }
+
+    #[inline]
+    pub const fn set_aging(&mut self, aging: i8) {
+        self.aging = Some(aging);
+    }
+
+    /// Drift samples, oldest first.
+    #[inline]
+    pub fn samples(&self) -> impl Iterator<Item = &DriftSample> {
+        self.samples.iter()
+    }
+
+    /// Records that the RTC was set from NTP.
+    ///
+    /// `at_us` is the NTP time in us, and `error_us` is RTC minus NTP time.
+    #[inline]
+    pub const fn record_set(&mut self, at_us: i64, error_us: i64) {
+        self.last_set = Some(SetPoint { at_us, error_us });
+    }
+
+    /// Forgets when the RTC was last set, e.g. after it was set by hand.
+    #[inline]
+    pub const fn forget_set(&mut self) {
+        self.last_set = None;
+    }
+
+    /// Adds a drift sample from a measurement against NTP.
+    ///
+    /// `at_us` is the NTP time in us, and `error_us` is RTC minus NTP time.
+    /// Returns [`None`] if the RTC was not set from NTP since boot, or too recently.
+    pub fn measure(&mut self, at_us: i64, error_us: i64) -> Option<DriftSample> {
+        let set = self.last_set.take()?;
+        let aging = self.aging?;
+
+        let elapsed_secs = at_us.saturating_sub(set.at_us).div_euclid(1_000_000);
+        if elapsed_secs < MIN_SAMPLE_SECS {
+            return None;
+        }
+
+        let sample = DriftSample {
+            timestamp: at_us.div_euclid(1_000_000),
+            elapsed_secs,
+            gained_us: error_us.saturating_sub(set.error_us),
+            aging,
+        };
+        if sample.ppb().abs() > MAX_DRIFT_PPB {
+            return None;
+        }
+
+        if self.samples.is_full() {
+            self.samples.pop_front();
+        }
+        // Cannot fail since a sample was just removed if full
+        let _ = self.samples.push_back(sample);
+        Some(sample)
+    }
+
+    /// Drift with the current aging offset in parts per billion, and how many seconds it covers.
+    pub fn drift(&self) -> Option<(i64, i64)> {
+        let aging = self.aging?;
+        let (gained_us, secs) = self.samples.iter().filter(|s| s.aging == aging).fold(
+            (0_i64, 0_i64),
+            |(gained, secs), s| {
+                (
+                    gained.saturating_add(s.gained_us),
+                    secs.saturating_add(s.elapsed_secs),
+                )
+            },
+        );
+
+        (secs > 0).then(|| (ppb(gained_us, secs), secs))
+    }
+
+    /// Returns the aging offset that cancels out the drift.
+    ///
+    /// Returns [`None`] if the samples with the current aging offset cover
+    /// less than [`MIN_TRIM_SECS`], or it is already as close as it gets.
+    pub fn trim(&self) -> Option<i8> {
+        let aging = self.aging?;
+        let (ppb, secs) = self.drift()?;
+        if secs < MIN_TRIM_SECS {
+            return None;
+        }
+
+        // Rounds half away from zero
+        let half = PPB_PER_STEP.div_euclid(2).saturating_mul(ppb.signum());
+        let steps = ppb.saturating_add(half).checked_div(PPB_PER_STEP)?;
+
+        let trimmed = i64::from(aging)
+            .saturating_add(steps)
+            .clamp(i64::from(i8::MIN), i64::from(i8::MAX));
+        let trimmed = i8::try_from(trimmed).ok()?;
+
+        (trimmed != aging).then_some(trimmed)
+    }
+}
+
+impl Default for DriftLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline]
+fn ppb(gained_us: i64, secs: i64) -> i64 {
+    gained_us
+        .saturating_mul(1_000)
+        .checked_div(secs)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_US: i64 = 60 * 60 * 1_000_000;
+
+    fn with_aging(aging: i8) -> DriftLog {
+        let mut log = DriftLog::new();
+        log.set_aging(aging);
+        log
+    }
+
+    #[test]
+    fn measures_ppb() {
+        let mut log = with_aging(0);
+        log.record_set(0, -2_000);
+
+        // Gained 36ms over 10 hours
+        let sample = log.measure(10 * HOUR_US, 34_000).expect("sample");
+        assert_eq!(sample.elapsed_secs, 36_000, "elapsed");
+        assert_eq!(sample.gained_us, 36_000, "error when set is subtracted");
+        assert_eq!(sample.ppb(), 1_000, "1 ppm fast");
+
+        assert_eq!(
+            log.measure(20 * HOUR_US, 0),
+            None,
+            "set point is used up by a measurement"
+        );
+    }
+
+    #[test]
+    fn short_intervals_are_ignored() {
+        let mut log = with_aging(0);
+        log.record_set(0, 0);
+        assert_eq!(log.measure(HOUR_US.div_euclid(2), 1_000), None, "too short");
+        assert_eq!(log.samples().count(), 0, "no samples");
+    }
+
+    #[test]
+    fn forgotten_set_is_ignored() {
+        let mut log = with_aging(0);
+        log.record_set(0, 0);
+        log.forget_set();
+        assert_eq!(log.measure(10 * HOUR_US, 1_000), None, "set by hand");
+    }
+
+    #[test]
+    fn implausible_drift_is_ignored() {
+        let mut log = with_aging(0);
+        log.record_set(0, 0);
+        assert_eq!(
+            log.measure(10 * HOUR_US, 3_600_000_000),
+            None,
+            "an hour off is not drift"
+        );
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut log = with_aging(0);
+        for i in 0..20 {
+            log.record_set(i * 2 * HOUR_US, 0);
+            log.measure((i * 2 + 1) * HOUR_US, 0).expect("sample");
+        }
+
+        assert_eq!(log.samples().count(), MAX_DRIFT_SAMPLES, "capped");
+        assert_eq!(
+            log.samples().next().map(|s| s.timestamp),
+            Some((4 * 2 + 1) * 3_600),
+            "oldest samples dropped first"
+        );
+    }
+
+    #[test]
+    fn drift_only_counts_current_aging() {
+        let mut log = with_aging(0);
+        log.record_set(0, 0);
+        log.measure(10 * HOUR_US, 36_000).expect("sample");
+
+        log.set_aging(10);
+        assert_eq!(log.drift(), None, "no samples with new aging");
+
+        log.record_set(10 * HOUR_US, 0);
+        log.measure(30 * HOUR_US, -7_200).expect("sample");
+        assert_eq!(log.drift(), Some((-100, 72_000)), "old samples ignored");
+    }
+
+    #[test]
+    fn trims_after_a_day() {
+        let mut log = with_aging(5);
+        log.record_set(0, 0);
+        // 1.26 ppm fast over 12 hours
+        log.measure(12 * HOUR_US, 54_432).expect("sample");
+        assert_eq!(log.trim(), None, "not enough data");
+
+        log.record_set(12 * HOUR_US, 0);
+        log.measure(24 * HOUR_US, 54_432).expect("sample");
+        assert_eq!(log.trim(), Some(18), "slowed down by 13 steps");
+    }
+
+    #[test]
+    fn trim_rounds_and_clamps() {
+        let mut log = with_aging(0);
+        log.record_set(0, 0);
+        // 0.04 ppm slow is closer to no trim than a step
+        log.measure(25 * HOUR_US, -3_600).expect("sample");
+        assert_eq!(log.trim(), None, "already as close as it gets");
+
+        let mut log = with_aging(-120);
+        log.record_set(0, 0);
+        // 2 ppm slow
+        log.measure(25 * HOUR_US, -180_000).expect("sample");
+        assert_eq!(log.trim(), Some(i8::MIN), "clamped");
+    }
+}
@@ -0,0 +1,449 @@
+//! # Local Alarms
+//! Converts alarms between local time, as entered by the user, and UTC, as stored in the DS3231.
+//!
+//! Shifting only the time of day is not enough once an alarm crosses midnight,
+//! since the weekday or date must move with it. Instead, the next occurrence of the
+//! alarm is found and converted, and the alarm is rebuilt from the converted datetime.
+//! The result is correct until the offset changes, e.g. at the next daylight saving time
+//! transition, so alarms that must follow local time are better scheduled with a
+//! [`Recurrence`](super::recurrence::Recurrence) instead.
+
+use chrono::{Datelike as _, NaiveDateTime, TimeDelta, Timelike as _};
+use ds3231::{Alarm1Config, Alarm2Config};
+
+use super::{alarm::next_occurrence, tz::PosixTz};
+
+/// Converts an alarm in local time to UTC.
+///
+/// `now` is in UTC and picks the occurrence, and thus the offset, used for the conversion.
+/// Returns [`None`] if the alarm never matches, e.g. 25:00.
+pub fn to_utc(local: &Alarm1Config, tz: &PosixTz, now: NaiveDateTime) -> Option<Alarm1Config> {
+    let now_local = now.checked_add_signed(TimeDelta::seconds(tz.offset_secs(now).into()))?;
+    let next = next_occurrence(local, now_local)?;
+
+    Some(rebuild(local, tz.to_utc(next)))
+}
+
+/// Converts an alarm in UTC to local time.
+///
+/// `now` is in UTC and picks the occurrence, and thus the offset, used for the conversion.
+pub fn to_local(utc: &Alarm1Config, tz: &PosixTz, now: NaiveDateTime) -> Option<Alarm1Config> {
+    let next = next_occurrence(utc, now)?;
+    let local = next.checked_add_signed(TimeDelta::seconds(tz.offset_secs(next).into()))?;
+
+    Some(rebuild(utc, local))
+}
+
+/// Converts an Alarm2 in local time to UTC. See [`to_utc`].
+pub fn alarm2_to_utc(
+    local: &Alarm2Config,
+    tz: &PosixTz,
+    now: NaiveDateTime,
+) -> Option<Alarm2Config> {
+    to_utc(&alarm2_as_alarm1(local), tz, now)
+        .as_ref()
+        .map(alarm1_as_alarm2)
+}
+
+/// Returns an alarm of the same kind as `config` that matches `dt`, in 24-hour format.
+fn rebuild(config: &Alarm1Config, dt: NaiveDateTime) -> Alarm1Config {
+    // Cannot truncate since they are within a day and month
+    let (hours, minutes, seconds) = (
+        dt.hour().truncate(),
+        dt.minute().truncate(),
+        dt.second().truncate(),
+    );
+
+    match config {
+        Alarm1Config::EverySecond => Alarm1Config::EverySecond,
+        Alarm1Config::AtSeconds { .. } => Alarm1Config::AtSeconds { seconds },
+        Alarm1Config::AtMinutesSeconds { .. } => {
+            Alarm1Config::AtMinutesSeconds { minutes, seconds }
+        }
+        Alarm1Config::AtTime { .. } => Alarm1Config::AtTime {
+            hours,
+            minutes,
+            seconds,
+            is_pm: None,
+        },
+        Alarm1Config::AtTimeOnDay { .. } => Alarm1Config::AtTimeOnDay {
+            hours,
+            minutes,
+            seconds,
+            // 1 to 7, so it cannot truncate
+            day: dt.weekday().number_from_sunday().truncate(),
+            is_pm: None,
+        },
+        Alarm1Config::AtTimeOnDate { .. } => Alarm1Config::AtTimeOnDate {
+            hours,
+            minutes,
+            seconds,
+            date: dt.day().truncate(),
+            is_pm: None,
+        },
+    }
+}
+
+/// Alarm2 has no seconds, so it is the matching Alarm1 at second 0.
+fn alarm2_as_alarm1(config: &Alarm2Config) -> Alarm1Config {
+    match *config {
+        Alarm2Config::EveryMinute => Alarm1Config::AtSeconds { seconds: 0 },
+        Alarm2Config::AtMinutes { minutes } => Alarm1Config::AtMinutesSeconds {
+            minutes,
+            seconds: 0,
+        },
+        Alarm2Config::AtTime {
+            hours,
+            minutes,
+            is_pm,
+        } => Alarm1Config::AtTime {
+            hours,
+            minutes,
+            seconds: 0,
+            is_pm,
+        },
+        Alarm2Config::AtTimeOnDay {
+            hours,
+            minutes,
+            day,
+            is_pm,
+        } => Alarm1Config::AtTimeOnDay {
+            hours,
+            minutes,
+            seconds: 0,
+            day,
+            is_pm,
+        },
+        Alarm2Config::AtTimeOnDate {
+            hours,
+            minutes,
+            date,
+            is_pm,
+        } => Alarm1Config::AtTimeOnDate {
+            hours,
+            minutes,
+            seconds: 0,
+            date,
+            is_pm,
+        },
+    }
+}
+
+/// The inverse of [`alarm2_as_alarm1`]. Seconds are dropped.
+fn alarm1_as_alarm2(config: &Alarm1Config) -> Alarm2Config {
+    match *config {
+        Alarm1Config::EverySecond | Alarm1Config::AtSeconds { .. } => Alarm2Config::EveryMinute,
+        Alarm1Config::AtMinutesSeconds { minutes, .. } => Alarm2Config::AtMinutes { minutes },
+        Alarm1Config::AtTime {
+            hours,
+            minutes,
+            is_pm,
+            ..
+        } => Alarm2Config::AtTime {
+            hours,
+            minutes,
+            is_pm,
+        },
+        Alarm1Config::AtTimeOnDay {
+            hours,
+            minutes,
+            day,
+            is_pm,
+            ..
+        } => Alarm2Config::AtTimeOnDay {
+            hours,
+            minutes,
+            day,
+            is_pm,
+        },
+        Alarm1Config::AtTimeOnDate {
+            hours,
+            minutes,
+            date,
+            is_pm,
+            ..
+        } => Alarm2Config::AtTimeOnDate {
+            hours,
+            minutes,
+            date,
+            is_pm,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, 0)
+            .unwrap()
+    }
+
+    fn tz(s: &str) -> PosixTz {
+        PosixTz::parse(s).unwrap()
+    }
+
+    #[test]
+    fn daily_crosses_midnight() {
+        let config = Alarm1Config::AtTime {
+            hours: 0,
+            minutes: 30,
+            seconds: 15,
+            is_pm: None,
+        };
+        let converted = to_utc(&config, &tz("CET-1"), utc(2024, 1, 10, 12, 0));
+        assert!(
+            matches!(
+                converted,
+                Some(Alarm1Config::AtTime {
+                    hours: 23,
+                    minutes: 30,
+                    seconds: 15,
+                    is_pm: None
+                })
+            ),
+            "{converted:?}"
+        );
+    }
+
+    #[test]
+    fn twelve_hour_input() {
+        let config = Alarm1Config::AtTime {
+            hours: 12,
+            minutes: 15,
+            seconds: 0,
+            is_pm: Some(false),
+        };
+        let converted = to_utc(&config, &tz("IST-5:30"), utc(2024, 1, 10, 12, 0));
+        assert!(
+            matches!(
+                converted,
+                Some(Alarm1Config::AtTime {
+                    hours: 18,
+                    minutes: 45,
+                    seconds: 0,
+                    is_pm: None
+                })
+            ),
+            "12:15 AM IST is 18:45 UTC the day before: {converted:?}"
+        );
+    }
+
+    #[test]
+    fn sunday_wraps_to_saturday() {
+        let config = Alarm1Config::AtTimeOnDay {
+            hours: 1,
+            minutes: 0,
+            seconds: 0,
+            day: 1,
+            is_pm: None,
+        };
+        let converted = to_utc(&config, &tz("AEST-10"), utc(2024, 1, 10, 12, 0));
+        assert!(
+            matches!(
+                converted,
+                Some(Alarm1Config::AtTimeOnDay {
+                    hours: 15,
+                    day: 7,
+                    ..
+                })
+            ),
+            "01:00 Sunday AEST is 15:00 Saturday UTC: {converted:?}"
+        );
+    }
+
+    #[test]
+    fn saturday_wraps_to_sunday() {
+        let config = Alarm1Config::AtTimeOnDay {
+            hours: 22,
+            minutes: 0,
+            seconds: 0,
+            day: 7,
+            is_pm: None,
+        };
+        let converted = to_utc(&config, &tz("EST5"), utc(2024, 1, 10, 12, 0));
+        assert!(
+            matches!(
+                converted,
+                Some(Alarm1Config::AtTimeOnDay {
+                    hours: 3,
+                    day: 1,
+                    ..
+                })
+            ),
+            "22:00 Saturday EST is 03:00 Sunday UTC: {converted:?}"
+        );
+    }
+
+    #[test]
+    fn first_wraps_to_month_end() {
+        let config = Alarm1Config::AtTimeOnDate {
+            hours: 0,
+            minutes: 30,
+            seconds: 0,
+            date: 1,
+            is_pm: None,
+        };
+        let cases = [
+            (utc(2024, 2, 10, 0, 0), 29, "leap year February"),
+            (utc(2023, 2, 10, 0, 0), 28, "February"),
+            (utc(2024, 4, 10, 0, 0), 30, "April"),
+            (utc(2024, 12, 10, 0, 0), 31, "December into the new year"),
+        ];
+
+        for (now, expected, case) in cases {
+            let converted = to_utc(&config, &tz("CET-1"), now);
+            assert!(
+                matches!(
+                    converted,
+                    Some(Alarm1Config::AtTimeOnDate { hours: 23, date, .. }) if date == expected
+                ),
+                "{case}: {converted:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn month_end_wraps_to_first() {
+        let config = Alarm1Config::AtTimeOnDate {
+            hours: 23,
+            minutes: 0,
+            seconds: 0,
+            date: 31,
+            is_pm: None,
+        };
+        // April has no 31st, so the next one is in May
+        let converted = to_utc(&config, &tz("EST5"), utc(2024, 4, 10, 0, 0));
+        assert!(
+            matches!(
+                converted,
+                Some(Alarm1Config::AtTimeOnDate {
+                    hours: 4,
+                    date: 1,
+                    ..
+                })
+            ),
+            "23:00 on the 31st EST is 04:00 on the 1st UTC: {converted:?}"
+        );
+    }
+
+    #[test]
+    fn uses_offset_of_next_occurrence() {
+        let config = Alarm1Config::AtTimeOnDate {
+            hours: 7,
+            minutes: 0,
+            seconds: 0,
+            date: 1,
+            is_pm: None,
+        };
+        let converted = to_utc(
+            &config,
+            &tz("CET-1CEST,M3.5.0,M10.5.0/3"),
+            utc(2024, 3, 20, 0, 0),
+        );
+        assert!(
+            matches!(
+                converted,
+                Some(Alarm1Config::AtTimeOnDate {
+                    hours: 5,
+                    date: 1,
+                    ..
+                })
+            ),
+            "April 1st is in CEST: {converted:?}"
+        );
+    }
+
+    #[test]
+    fn sub_hour_offsets() {
+        let config = Alarm1Config::AtMinutesSeconds {
+            minutes: 10,
+            seconds: 5,
+        };
+        let converted = to_utc(&config, &tz("<+0545>-5:45"), utc(2024, 1, 10, 12, 0));
+        assert!(
+            matches!(
+                converted,
+                Some(Alarm1Config::AtMinutesSeconds {
+                    minutes: 25,
+                    seconds: 5
+                })
+            ),
+            "{converted:?}"
+        );
+
+        let config = Alarm1Config::AtSeconds { seconds: 42 };
+        let converted = to_utc(&config, &tz("<+0545>-5:45"), utc(2024, 1, 10, 12, 0));
+        assert!(
+            matches!(converted, Some(Alarm1Config::AtSeconds { seconds: 42 })),
+            "{converted:?}"
+        );
+        assert!(
+            matches!(
+                to_utc(
+                    &Alarm1Config::EverySecond,
+                    &tz("CET-1"),
+                    utc(2024, 1, 1, 0, 0)
+                ),
+                Some(Alarm1Config::EverySecond)
+            ),
+            "every second is unchanged"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let tz = tz("AEST-10AEDT,M10.1.0,M4.1.0/3");
+        let now = utc(2024, 6, 30, 20, 0);
+        let config = Alarm1Config::AtTimeOnDay {
+            hours: 6,
+            minutes: 45,
+            seconds: 0,
+            day: 2,
+            is_pm: None,
+        };
+
+        let back = to_utc(&config, &tz, now).and_then(|utc| to_local(&utc, &tz, now));
+        assert!(
+            matches!(
+                back,
+                Some(Alarm1Config::AtTimeOnDay {
+                    hours: 6,
+                    minutes: 45,
+                    seconds: 0,
+                    day: 2,
+                    is_pm: None
+                })
+            ),
+            "{back:?}"
+        );
+    }
+
+    #[test]
+    fn alarm2() {
+        let config = Alarm2Config::AtTimeOnDay {
+            hours: 0,
+            minutes: 5,
+            day: 1,
+            is_pm: None,
+        };
+        let converted = alarm2_to_utc(&config, &tz("CET-1"), utc(2024, 1, 10, 12, 0));
+        assert!(
+            matches!(
+                converted,
+                Some(Alarm2Config::AtTimeOnDay {
+                    hours: 23,
+                    minutes: 5,
+                    day: 7,
+                    is_pm: None
+                })
+            ),
+            "{converted:?}"
+        );
+    }
+}
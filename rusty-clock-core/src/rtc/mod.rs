@@ -0,0 +1,9 @@
+//! # RTC
+//! Alarms, time zones and drift of the DS3231.
+
+pub mod alarm;
+pub mod drift;
+pub mod local_alarm;
+pub mod recurrence;
+pub mod registers;
+pub mod tz;
@@ -0,0 +1,380 @@
+//! # Recurrence
+//! This module provides the [`Recurrence`] rules for scheduled alarms.
+//!
+//! The DS3231 can only repeat an alarm daily, weekly on a single day, or monthly
+//! on a single date. Instead, the next firing instant is worked out here and
+//! Alarm1 is programmed to match it exactly.
+
+use chrono::{Datelike as _, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Weekday};
+
+/// The maximum number of dates a [`Recurrence::Dates`] can hold.
+pub const MAX_DATES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RecurrenceError {
+    #[error("Unknown recurrence")]
+    Unknown,
+    #[error("Invalid weekday")]
+    InvalidWeekday,
+    #[error("Invalid interval")]
+    InvalidInterval,
+    #[error("Invalid date")]
+    InvalidDate,
+    #[error("Too many dates")]
+    TooManyDates,
+}
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// A bitmask of weekdays.
+///
+/// Bit 0 is Monday and bit 6 is Sunday.
+pub struct WeekdayMask(u8);
+
+impl WeekdayMask {
+    /// Monday to Friday.
+    pub const WEEKDAYS: Self = Self(0b001_1111);
+    /// Saturday and Sunday.
+    pub const WEEKENDS: Self = Self(0b110_0000);
+
+    /// Create a new [`WeekdayMask`].
+    ///
+    /// Returns `None` if no day is set or bit 7 is set.
+    #[inline]
+    pub const fn new(bits: u8) -> Option<Self> {
+        if bits == 0 || bits > 0b111_1111 {
+            None
+        } else {
+            Some(Self(bits))
+        }
+    }
+
+    /// Returns the bit representing `day`.
+    #[inline]
+    pub fn bit(day: Weekday) -> u8 {
+        1_u8.wrapping_shl(day.num_days_from_monday())
+    }
+
+    #[inline]
+    pub fn contains(self, day: Weekday) -> bool {
+        self.0 & Self::bit(day) != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// How often an alarm repeats.
+pub enum Recurrence {
+    /// Fires once, then disables itself.
+    Once,
+    /// Fires every day.
+    Daily,
+    /// Fires on every day set in the mask.
+    Weekly(WeekdayMask),
+    /// Fires every `interval` days, counting from `start`.
+    EveryNDays { interval: u16, start: NaiveDate },
+    /// Fires on each of the given dates.
+    Dates(heapless::Vec<NaiveDate, MAX_DATES>),
+}
+
+impl Recurrence {
+    /// Returns the next datetime, strictly after `now`, at which an alarm
+    /// set at `time` fires.
+    ///
+    /// Returns `None` if it never fires again.
+    pub fn next_after(&self, time: NaiveTime, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let today = now.date();
+
+        match self {
+            Self::Once | Self::Daily => Self::first_from(today, 2, now, time, |_| true),
+            Self::Weekly(mask) => {
+                Self::first_from(today, 8, now, time, |date| mask.contains(date.weekday()))
+            }
+            Self::EveryNDays { interval, start } => {
+                let interval = i64::from(*interval);
+                if interval == 0 {
+                    return None;
+                }
+
+                let first = if today <= *start {
+                    *start
+                } else {
+                    let elapsed = today.signed_duration_since(*start).num_days();
+                    // `elapsed` and `interval` are both positive
+                    let periods = elapsed.cast_unsigned().div_ceil(interval.cast_unsigned());
+                    let days = periods.checked_mul(interval.cast_unsigned())?;
+                    start.checked_add_signed(TimeDelta::days(days.cast_signed()))?
+                };
+
+                let candidate = first.and_time(time);
+                if candidate > now {
+                    Some(candidate)
+                } else {
+                    first
+                        .checked_add_signed(TimeDelta::days(interval))
+                        .map(|d| d.and_time(time))
+                }
+            }
+            Self::Dates(dates) => dates
+                .iter()
+                .map(|date| date.and_time(time))
+                .filter(|dt| *dt > now)
+                .min(),
+        }
+    }
+
+    /// Finds the first date within `days` days from `from` that matches
+    /// `predicate` and whose datetime is after `now`.
+    fn first_from(
+        from: NaiveDate,
+        days: u8,
+        now: NaiveDateTime,
+        time: NaiveTime,
+        predicate: impl Fn(NaiveDate) -> bool,
+    ) -> Option<NaiveDateTime> {
+        from.iter_days()
+            .take(days.into())
+            .filter(|date| predicate(*date))
+            .map(|date| date.and_time(time))
+            .find(|dt| *dt > now)
+    }
+}
+
+/// Parses a 3-letter weekday, e.g. `mon`.
+fn parse_weekday(s: &str) -> Result<Weekday, RecurrenceError> {
+    match s {
+        "mon" => Ok(Weekday::Mon),
+        "tue" => Ok(Weekday::Tue),
+        "wed" => Ok(Weekday::Wed),
+        "thu" => Ok(Weekday::Thu),
+        "fri" => Ok(Weekday::Fri),
+        "sat" => Ok(Weekday::Sat),
+        "sun" => Ok(Weekday::Sun),
+        _ => Err(RecurrenceError::InvalidWeekday),
+    }
+}
+
+#[inline]
+fn parse_date(s: &str) -> Result<NaiveDate, RecurrenceError> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| RecurrenceError::InvalidDate)
+}
+
+/// Parses a [`Recurrence`] from one of the following formats:
+/// - `once`, `daily`, `weekdays`, `weekends`
+/// - `days:mon,wed,fri`
+/// - `every:3:2026-01-31`, i.e. every 3 days starting from 31st of January 2026
+/// - `dates:2026-12-24,2026-12-31`
+impl core::str::FromStr for Recurrence {
+    type Err = RecurrenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "once" => return Ok(Self::Once),
+            "daily" => return Ok(Self::Daily),
+            "weekdays" => return Ok(Self::Weekly(WeekdayMask::WEEKDAYS)),
+            "weekends" => return Ok(Self::Weekly(WeekdayMask::WEEKENDS)),
+            _ => {}
+        }
+
+        let (kind, args) = s.split_once(':').ok_or(RecurrenceError::Unknown)?;
+
+        match kind {
+            "days" => {
+                let bits = args.split(',').try_fold(0_u8, |bits, day| {
+                    parse_weekday(day).map(|d| bits | WeekdayMask::bit(d))
+                })?;
+
+                WeekdayMask::new(bits)
+                    .map(Self::Weekly)
+                    .ok_or(RecurrenceError::InvalidWeekday)
+            }
+            "every" => {
+                let (interval, start) = args.split_once(':').ok_or(RecurrenceError::Unknown)?;
+                let interval: u16 = interval
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or(RecurrenceError::InvalidInterval)?;
+
+                Ok(Self::EveryNDays {
+                    interval,
+                    start: parse_date(start)?,
+                })
+            }
+            "dates" => {
+                let mut dates = heapless::Vec::new();
+                for date in args.split(',') {
+                    dates
+                        .push(parse_date(date)?)
+                        .map_err(|_| RecurrenceError::TooManyDates)?;
+                }
+
+                Ok(Self::Dates(dates))
+            }
+            _ => Err(RecurrenceError::Unknown),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dt(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .and_then(|date| date.and_hms_opt(h, min, 0))
+            .expect("valid datetime")
+    }
+
+    fn time(h: u32, min: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, min, 0).expect("valid time")
+    }
+
+    #[test]
+    fn daily_later_today() {
+        // Saturday 17th October 2026
+        let now = dt(2026, 10, 17, 5, 0);
+        let next = Recurrence::Daily.next_after(time(6, 30), now);
+        assert_eq!(next, Some(dt(2026, 10, 17, 6, 30)), "should fire today");
+    }
+
+    #[test]
+    fn daily_wraps_to_tomorrow() {
+        let now = dt(2026, 10, 17, 6, 30);
+        let next = Recurrence::Daily.next_after(time(6, 30), now);
+        assert_eq!(
+            next,
+            Some(dt(2026, 10, 18, 6, 30)),
+            "must be strictly after now"
+        );
+    }
+
+    #[test]
+    fn weekdays_skip_weekend() {
+        // Friday after the alarm went off
+        let now = dt(2026, 10, 16, 7, 0);
+        let next = Recurrence::Weekly(WeekdayMask::WEEKDAYS).next_after(time(6, 30), now);
+        assert_eq!(next, Some(dt(2026, 10, 19, 6, 30)), "should skip to Monday");
+    }
+
+    #[test]
+    fn weekends_from_monday() {
+        let now = dt(2026, 10, 19, 12, 0);
+        let next = Recurrence::Weekly(WeekdayMask::WEEKENDS).next_after(time(9, 0), now);
+        assert_eq!(
+            next,
+            Some(dt(2026, 10, 24, 9, 0)),
+            "should skip to Saturday"
+        );
+    }
+
+    #[test]
+    fn single_weekday_wraps_full_week() {
+        // Wednesday, after the alarm time
+        let now = dt(2026, 10, 21, 10, 0);
+        let mask = WeekdayMask::new(WeekdayMask::bit(Weekday::Wed)).expect("valid");
+        let next = Recurrence::Weekly(mask).next_after(time(9, 0), now);
+        assert_eq!(
+            next,
+            Some(dt(2026, 10, 28, 9, 0)),
+            "should be next Wednesday"
+        );
+    }
+
+    #[test]
+    fn every_n_days_before_start() {
+        let start = NaiveDate::from_ymd_opt(2026, 11, 1).expect("valid date");
+        let rule = Recurrence::EveryNDays { interval: 3, start };
+        let next = rule.next_after(time(8, 0), dt(2026, 10, 17, 12, 0));
+        assert_eq!(
+            next,
+            Some(dt(2026, 11, 1, 8, 0)),
+            "should fire on start date"
+        );
+    }
+
+    #[test]
+    fn every_n_days_rounds_up_to_period() {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 1).expect("valid date");
+        let rule = Recurrence::EveryNDays { interval: 3, start };
+        // 16 days after start, next period is at day 18
+        let next = rule.next_after(time(8, 0), dt(2026, 10, 17, 12, 0));
+        assert_eq!(
+            next,
+            Some(dt(2026, 10, 19, 8, 0)),
+            "should fire on the next period"
+        );
+    }
+
+    #[test]
+    fn every_n_days_on_period_after_time() {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 1).expect("valid date");
+        let rule = Recurrence::EveryNDays { interval: 2, start };
+        // Day 16 is a period, but the alarm already went off
+        let next = rule.next_after(time(8, 0), dt(2026, 10, 17, 12, 0));
+        assert_eq!(
+            next,
+            Some(dt(2026, 10, 19, 8, 0)),
+            "should skip a whole period"
+        );
+    }
+
+    #[test]
+    fn every_n_days_crosses_month() {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 30).expect("valid date");
+        let rule = Recurrence::EveryNDays { interval: 7, start };
+        let next = rule.next_after(time(8, 0), dt(2026, 10, 31, 0, 0));
+        assert_eq!(next, Some(dt(2026, 11, 6, 8, 0)), "should cross month end");
+    }
+
+    #[test]
+    fn dates_picks_earliest_future() {
+        let rule: Recurrence = "dates:2026-12-31,2026-10-01,2026-12-24"
+            .parse()
+            .expect("valid recurrence");
+        let next = rule.next_after(time(7, 0), dt(2026, 10, 17, 0, 0));
+        assert_eq!(
+            next,
+            Some(dt(2026, 12, 24, 7, 0)),
+            "should ignore past dates"
+        );
+    }
+
+    #[test]
+    fn dates_all_past() {
+        let rule: Recurrence = "dates:2026-01-01".parse().expect("valid recurrence");
+        let next = rule.next_after(time(7, 0), dt(2026, 10, 17, 0, 0));
+        assert_eq!(next, None, "should never fire again");
+    }
+
+    #[test]
+    fn parse_formats() {
+        assert_eq!(
+            "weekdays".parse(),
+            Ok(Recurrence::Weekly(WeekdayMask::WEEKDAYS)),
+            "weekdays"
+        );
+        assert_eq!(
+            "days:sat,sun".parse(),
+            Ok(Recurrence::Weekly(WeekdayMask::WEEKENDS)),
+            "custom mask"
+        );
+        assert_eq!(
+            "every:0:2026-10-17".parse::<Recurrence>(),
+            Err(RecurrenceError::InvalidInterval),
+            "zero interval"
+        );
+        assert_eq!(
+            "days:funday".parse::<Recurrence>(),
+            Err(RecurrenceError::InvalidWeekday),
+            "bad weekday"
+        );
+        assert_eq!(
+            "dates:2026-02-30".parse::<Recurrence>(),
+            Err(RecurrenceError::InvalidDate),
+            "bad date"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! # DS3231 Alarm Registers
+//! This module decodes the raw Alarm1 registers, which
+//! the [`ds3231`] crate cannot read back.
+
+use ds3231::Alarm1Config;
+
+/// Alarm mask bit (`AxMx`), the MSB of each alarm register.
+const MASK_BIT: u8 = 0b1000_0000;
+/// Day/Date select bit of the day/date register. Day of week if set.
+const DY_DT_BIT: u8 = 0b0100_0000;
+/// 12/24-hour select bit of the hours register. 12-hour mode if set.
+const TWELVE_HOUR_BIT: u8 = 0b0100_0000;
+/// AM/PM bit of the hours register in 12-hour mode. PM if set.
+const PM_BIT: u8 = 0b0010_0000;
+
+/// Decodes a binary-coded decimal.
+///
+/// Returns `None` if either digit is greater than 9 or
+/// the value is greater than `max`.
+#[inline]
+fn from_bcd(value: u8, max: u8) -> Option<u8> {
+    let tens = value.wrapping_shr(4);
+    let ones = value & 0x0F;
+
+    if tens > 9 || ones > 9 {
+        return None;
+    }
+
+    Some(tens.saturating_mul(10).saturating_add(ones)).filter(|v| *v <= max)
+}
+
+/// Decodes the hours register into hours and the PM flag.
+///
+/// PM flag is `None` in 24-hour mode.
+#[inline]
+fn decode_hours(reg: u8) -> Option<(u8, Option<bool>)> {
+    if reg & TWELVE_HOUR_BIT == 0 {
+        Some((from_bcd(reg & 0b0011_1111, 23)?, None))
+    } else {
+        let hours = from_bcd(reg & 0b0001_1111, 12).filter(|h| *h >= 1)?;
+        Some((hours, Some(reg & PM_BIT != 0)))
+    }
+}
+
+/// Decodes the Alarm1 registers into an [`Alarm1Config`].
+///
+/// `regs` are the seconds, minutes, hours and day/date registers in order.
+/// Returns `None` if the mask bits do not form a valid alarm rate or
+/// any of the values are out of range.
+pub fn decode_alarm1(regs: [u8; 4]) -> Option<Alarm1Config> {
+    let [sec_reg, min_reg, hour_reg, day_reg] = regs;
+
+    let masks = (
+        sec_reg & MASK_BIT != 0,
+        min_reg & MASK_BIT != 0,
+        hour_reg & MASK_BIT != 0,
+        day_reg & MASK_BIT != 0,
+    );
+
+    let seconds = || from_bcd(sec_reg & !MASK_BIT, 59);
+    let minutes = || from_bcd(min_reg & !MASK_BIT, 59);
+    let hours = || decode_hours(hour_reg & !MASK_BIT);
+
+    let config = match masks {
+        (true, true, true, true) => Alarm1Config::EverySecond,
+        (false, true, true, true) => Alarm1Config::AtSeconds {
+            seconds: seconds()?,
+        },
+        (false, false, true, true) => Alarm1Config::AtMinutesSeconds {
+            minutes: minutes()?,
+            seconds: seconds()?,
+        },
+        (false, false, false, true) => {
+            let (hours, is_pm) = hours()?;
+            Alarm1Config::AtTime {
+                hours,
+                minutes: minutes()?,
+                seconds: seconds()?,
+                is_pm,
+            }
+        }
+        (false, false, false, false) => {
+            let (hours, is_pm) = hours()?;
+
+            if day_reg & DY_DT_BIT == 0 {
+                Alarm1Config::AtTimeOnDate {
+                    hours,
+                    minutes: minutes()?,
+                    seconds: seconds()?,
+                    date: from_bcd(day_reg & 0b0011_1111, 31).filter(|d| *d >= 1)?,
+                    is_pm,
+                }
+            } else {
+                Alarm1Config::AtTimeOnDay {
+                    hours,
+                    minutes: minutes()?,
+                    seconds: seconds()?,
+                    day: from_bcd(day_reg & 0b0000_1111, 7).filter(|d| *d >= 1)?,
+                    is_pm,
+                }
+            }
+        }
+        _ => return None,
+    };
+
+    Some(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_second() {
+        let config = decode_alarm1([0x80, 0x80, 0x80, 0x80]);
+        assert!(
+            matches!(config, Some(Alarm1Config::EverySecond)),
+            "all masks set"
+        );
+    }
+
+    #[test]
+    fn at_seconds() {
+        let config = decode_alarm1([0x45, 0x80, 0x80, 0x80]);
+        assert!(
+            matches!(config, Some(Alarm1Config::AtSeconds { seconds: 45 })),
+            "{config:?}"
+        );
+    }
+
+    #[test]
+    fn at_minutes_seconds() {
+        let config = decode_alarm1([0x30, 0x59, 0x80, 0x80]);
+        assert!(
+            matches!(
+                config,
+                Some(Alarm1Config::AtMinutesSeconds {
+                    minutes: 59,
+                    seconds: 30
+                })
+            ),
+            "{config:?}"
+        );
+    }
+
+    #[test]
+    fn daily_24_hour() {
+        let config = decode_alarm1([0x00, 0x30, 0x06, 0x80]);
+        assert!(
+            matches!(
+                config,
+                Some(Alarm1Config::AtTime {
+                    hours: 6,
+                    minutes: 30,
+                    seconds: 0,
+                    is_pm: None
+                })
+            ),
+            "{config:?}"
+        );
+
+        let config = decode_alarm1([0x00, 0x00, 0x23, 0x80]);
+        assert!(
+            matches!(config, Some(Alarm1Config::AtTime { hours: 23, .. })),
+            "20-hour bit is a tens digit in 24-hour mode: {config:?}"
+        );
+    }
+
+    #[test]
+    fn daily_12_hour() {
+        // 12-hour mode, PM, 11 o'clock
+        let config = decode_alarm1([0x00, 0x15, 0x71, 0x80]);
+        assert!(
+            matches!(
+                config,
+                Some(Alarm1Config::AtTime {
+                    hours: 11,
+                    minutes: 15,
+                    seconds: 0,
+                    is_pm: Some(true)
+                })
+            ),
+            "{config:?}"
+        );
+
+        // 12-hour mode, AM, 12 o'clock
+        let config = decode_alarm1([0x00, 0x00, 0x52, 0x80]);
+        assert!(
+            matches!(
+                config,
+                Some(Alarm1Config::AtTime {
+                    hours: 12,
+                    is_pm: Some(false),
+                    ..
+                })
+            ),
+            "{config:?}"
+        );
+    }
+
+    #[test]
+    fn on_date() {
+        let config = decode_alarm1([0x00, 0x00, 0x07, 0x31]);
+        assert!(
+            matches!(
+                config,
+                Some(Alarm1Config::AtTimeOnDate {
+                    hours: 7,
+                    date: 31,
+                    ..
+                })
+            ),
+            "{config:?}"
+        );
+    }
+
+    #[test]
+    fn on_day() {
+        let config = decode_alarm1([0x00, 0x00, 0x07, 0x47]);
+        assert!(
+            matches!(
+                config,
+                Some(Alarm1Config::AtTimeOnDay {
+                    hours: 7,
+                    day: 7,
+                    ..
+                })
+            ),
+            "{config:?}"
+        );
+    }
+
+    #[test]
+    fn invalid_mask_combination() {
+        // A1M2 set without A1M3 and A1M4
+        let config = decode_alarm1([0x00, 0x80, 0x00, 0x00]);
+        assert!(config.is_none(), "{config:?}");
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert!(
+            decode_alarm1([0x60, 0x80, 0x80, 0x80]).is_none(),
+            "60 seconds"
+        );
+        assert!(
+            decode_alarm1([0x0A, 0x80, 0x80, 0x80]).is_none(),
+            "non-BCD digit"
+        );
+        assert!(
+            decode_alarm1([0x00, 0x00, 0x24, 0x80]).is_none(),
+            "24 hours"
+        );
+        assert!(decode_alarm1([0x00, 0x00, 0x07, 0x00]).is_none(), "date 0");
+        assert!(decode_alarm1([0x00, 0x00, 0x07, 0x48]).is_none(), "day 8");
+    }
+}
@@ -0,0 +1,556 @@
+//! # Time Zones
+//! Runtime time zones with daylight saving time, defined by POSIX TZ strings.
+//!
+//! A TZ string holds the standard time, and optionally the daylight saving time
+//! with the rules for when it starts and ends, e.g. `CET-1CEST,M3.5.0,M10.5.0/3`.
+//! Offsets are west of UTC, so `CET-1` is UTC+1.
+
+use chrono::{Datelike as _, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+
+/// The longest TZ string that is accepted.
+pub const MAX_TZ_LENGTH: usize = 64;
+
+/// Simply an alias [`heapless::String`] used for TZ strings.
+pub type TzString = heapless::String<MAX_TZ_LENGTH>;
+
+/// Offsets must be less than a day to be a valid [`FixedOffset`].
+const MAX_OFFSET_SECS: i32 = 24 * 3600 - 1;
+
+/// Transition times may be up to a week away from midnight.
+const MAX_TRANSITION_SECS: i32 = 167 * 3600;
+
+/// Transitions happen at 02:00 local time unless given.
+const DEFAULT_TRANSITION_SECS: i32 = 2 * 3600;
+
+/// Used if an offset is somehow out of range.
+const UTC: FixedOffset = FixedOffset::east_opt(0).unwrap();
+
+/// Daylight saving time rules used when a TZ string has none, as done by glibc.
+const DEFAULT_RULES: &str = "M3.2.0,M11.1.0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TzError {
+    #[error("Invalid time zone name")]
+    InvalidName,
+    #[error("Invalid UTC offset")]
+    InvalidOffset,
+    #[error("Invalid daylight saving time rule")]
+    InvalidRule,
+    #[error("Unexpected characters at the end")]
+    TrailingCharacters,
+    #[error("TZ string is too long")]
+    TooLong,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The day of the year a transition happens on.
+enum RuleDate {
+    /// `Jn`: Day 1 to 365. February 29 is never counted.
+    Julian(u16),
+    /// `n`: Day 0 to 365. February 29 is counted in leap years.
+    ZeroBased(u16),
+    /// `Mm.w.d`: Day `d` (0 is Sunday) of week `w` of month `m`.
+    /// Week 5 is the last `d` of the month.
+    MonthWeekDay { month: u8, week: u8, weekday: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// When daylight saving time starts or ends.
+struct Transition {
+    date: RuleDate,
+    /// Seconds after local midnight.
+    time: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Dst {
+    /// Seconds east of UTC.
+    offset: i32,
+    /// In local standard time.
+    start: Transition,
+    /// In local daylight saving time.
+    end: Transition,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A time zone parsed from a POSIX TZ string.
+pub struct PosixTz {
+    /// Seconds east of UTC.
+    std_offset: i32,
+    dst: Option<Dst>,
+}
+
+impl PosixTz {
+    /// A time zone without daylight saving time, `hours` east of UTC.
+    #[inline]
+    pub const fn fixed(hours: i8) -> Self {
+        Self {
+            std_offset: i32::from(hours).saturating_mul(3600),
+            dst: None,
+        }
+    }
+
+    /// Parses a POSIX TZ string such as `CET-1CEST,M3.5.0,M10.5.0/3`.
+    ///
+    /// # Errors
+    /// Returns the first part of the string that is invalid.
+    pub fn parse(s: &str) -> Result<Self, TzError> {
+        let mut p = Parser(s.as_bytes());
+
+        p.name()?;
+        let std_offset = p.offset()?;
+        if p.is_empty() {
+            return Ok(Self {
+                std_offset,
+                dst: None,
+            });
+        }
+
+        p.name()?;
+        let offset = if p.peek().is_some_and(|c| c != b',') {
+            p.offset()?
+        } else {
+            std_offset.saturating_add(3600)
+        };
+
+        let (start, end) = if p.eat(b',') {
+            p.rules()?
+        } else {
+            Parser(DEFAULT_RULES.as_bytes()).rules()?
+        };
+
+        if !p.is_empty() {
+            return Err(TzError::TrailingCharacters);
+        }
+
+        Ok(Self {
+            std_offset,
+            dst: Some(Dst { offset, start, end }),
+        })
+    }
+
+    /// Returns the offset in seconds east of UTC at `utc`.
+    pub fn offset_secs(&self, utc: NaiveDateTime) -> i32 {
+        let Some(dst) = self.dst else {
+            return self.std_offset;
+        };
+
+        let year = utc.year();
+        let (Some(start), Some(end)) = (
+            dst.start.utc_in(year, self.std_offset),
+            dst.end.utc_in(year, dst.offset),
+        ) else {
+            return self.std_offset;
+        };
+
+        // Daylight saving time spans the new year in the southern hemisphere
+        let is_dst = if start <= end {
+            start <= utc && utc < end
+        } else {
+            utc < end || start <= utc
+        };
+
+        if is_dst { dst.offset } else { self.std_offset }
+    }
+
+    /// Returns the offset at `utc`.
+    #[inline]
+    pub fn offset(&self, utc: NaiveDateTime) -> FixedOffset {
+        // Offsets are validated while parsing
+        FixedOffset::east_opt(self.offset_secs(utc)).unwrap_or(UTC)
+    }
+
+    /// Converts a local datetime to UTC.
+    ///
+    /// Times repeated when the clocks go back resolve to the earlier one.
+    /// Times skipped when the clocks go forward are moved forward by the gap.
+    pub fn to_utc(self, local: NaiveDateTime) -> NaiveDateTime {
+        let candidate = |offset: i32| {
+            let utc = local.checked_sub_signed(TimeDelta::seconds(offset.into()))?;
+            (self.offset_secs(utc) == offset).then_some(utc)
+        };
+
+        let std = candidate(self.std_offset);
+        let dst = self.dst.and_then(|dst| candidate(dst.offset));
+
+        match (std, dst) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(utc), None) | (None, Some(utc)) => utc,
+            // In the gap, so the standard offset lands after the transition
+            (None, None) => local
+                .checked_sub_signed(TimeDelta::seconds(self.std_offset.into()))
+                .unwrap_or(local),
+        }
+    }
+}
+
+impl Transition {
+    /// Returns when the transition happens in `year` in UTC,
+    /// given the `offset` in effect before it.
+    fn utc_in(self, year: i32, offset: i32) -> Option<NaiveDateTime> {
+        let date = self.date.in_year(year)?;
+        let secs = i64::from(self.time).checked_sub(offset.into())?;
+
+        date.and_time(NaiveTime::MIN)
+            .checked_add_signed(TimeDelta::seconds(secs))
+    }
+}
+
+impl RuleDate {
+    fn in_year(self, year: i32) -> Option<NaiveDate> {
+        let jan1 = NaiveDate::from_ymd_opt(year, 1, 1)?;
+
+        match self {
+            Self::Julian(day) => {
+                let is_leap = NaiveDate::from_ymd_opt(year, 2, 29).is_some();
+                // Skips February 29, which is day 60 in a leap year
+                let skip = i64::from(is_leap && day >= 60);
+                let days = i64::from(day).checked_sub(1)?.checked_add(skip)?;
+                jan1.checked_add_signed(TimeDelta::days(days))
+            }
+            Self::ZeroBased(day) => jan1.checked_add_signed(TimeDelta::days(day.into())),
+            Self::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = NaiveDate::from_ymd_opt(year, month.into(), 1)?;
+                let first_weekday = first.weekday().num_days_from_sunday();
+                let until_weekday = u32::from(weekday)
+                    .checked_add(7)?
+                    .checked_sub(first_weekday)?
+                    .rem_euclid(7);
+
+                // Week 5 means the last one, which may be the 4th
+                (0..u32::from(week)).rev().find_map(|w| {
+                    let day = w
+                        .checked_mul(7)?
+                        .checked_add(until_weekday)?
+                        .checked_add(1)?;
+                    NaiveDate::from_ymd_opt(year, month.into(), day)
+                })
+            }
+        }
+    }
+}
+
+/// A cursor over the bytes of a TZ string.
+struct Parser<'a>(&'a [u8]);
+
+impl Parser<'_> {
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    fn peek(&self) -> Option<u8> {
+        self.0.first().copied()
+    }
+
+    /// Skips `c` if it is next.
+    fn eat(&mut self, c: u8) -> bool {
+        match self.0.split_first() {
+            Some((&first, rest)) if first == c => {
+                self.0 = rest;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Skips bytes while `pred` holds and returns how many were skipped.
+    fn skip_while(&mut self, pred: impl Fn(u8) -> bool) -> usize {
+        let len = self.0.iter().take_while(|c| pred(**c)).count();
+        self.0 = self.0.get(len..).unwrap_or_default();
+        len
+    }
+
+    /// Parses a name of at least 3 letters, or anything quoted in `<>` such as `<+0545>`.
+    fn name(&mut self) -> Result<(), TzError> {
+        if self.eat(b'<') {
+            let len = self.skip_while(|c| c.is_ascii_alphanumeric() || c == b'+' || c == b'-');
+            if len < 3 || !self.eat(b'>') {
+                return Err(TzError::InvalidName);
+            }
+        } else if self.skip_while(|c| c.is_ascii_alphabetic()) < 3 {
+            return Err(TzError::InvalidName);
+        }
+
+        Ok(())
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let digits = self.0.iter().take_while(|c| c.is_ascii_digit()).count();
+        let (num, rest) = self.0.split_at_checked(digits)?;
+        self.0 = rest;
+
+        // Only digits, so it is valid UTF-8
+        let num = core::str::from_utf8(num).ok()?;
+        num.parse().ok()
+    }
+
+    /// Parses `[+-]hh[:mm[:ss]]` into seconds, up to `max_hours`.
+    fn duration(&mut self, max_hours: u32) -> Option<i32> {
+        let negative = self.eat(b'-');
+        if !negative {
+            self.eat(b'+');
+        }
+
+        let hours = self.number().filter(|h| *h <= max_hours)?;
+        let mins = if self.eat(b':') {
+            self.number().filter(|m| *m < 60)?
+        } else {
+            0
+        };
+        let secs = if self.eat(b':') {
+            self.number().filter(|s| *s < 60)?
+        } else {
+            0
+        };
+
+        let total = hours
+            .checked_mul(3600)?
+            .checked_add(mins.checked_mul(60)?)?
+            .checked_add(secs)?;
+        let total = i32::try_from(total).ok()?;
+
+        if negative {
+            total.checked_neg()
+        } else {
+            Some(total)
+        }
+    }
+
+    /// Parses a UTC offset into seconds east of UTC.
+    fn offset(&mut self) -> Result<i32, TzError> {
+        self.duration(24)
+            // POSIX offsets are west of UTC
+            .and_then(i32::checked_neg)
+            .filter(|o| (-MAX_OFFSET_SECS..=MAX_OFFSET_SECS).contains(o))
+            .ok_or(TzError::InvalidOffset)
+    }
+
+    fn transition(&mut self) -> Option<Transition> {
+        let date = if self.eat(b'J') {
+            RuleDate::Julian(self.number().filter(|d| (1..=365).contains(d))?.truncate())
+        } else if self.eat(b'M') {
+            let month = self.number().filter(|m| (1..=12).contains(m))?;
+            self.eat(b'.').then_some(())?;
+            let week = self.number().filter(|w| (1..=5).contains(w))?;
+            self.eat(b'.').then_some(())?;
+            let weekday = self.number().filter(|d| *d <= 6)?;
+
+            RuleDate::MonthWeekDay {
+                month: month.truncate(),
+                week: week.truncate(),
+                weekday: weekday.truncate(),
+            }
+        } else {
+            RuleDate::ZeroBased(self.number().filter(|d| *d <= 365)?.truncate())
+        };
+
+        let time = if self.eat(b'/') {
+            self.duration(167)
+                .filter(|t| (-MAX_TRANSITION_SECS..=MAX_TRANSITION_SECS).contains(t))?
+        } else {
+            DEFAULT_TRANSITION_SECS
+        };
+
+        Some(Transition { date, time })
+    }
+
+    /// Parses the `start[/time],end[/time]` rules.
+    fn rules(&mut self) -> Result<(Transition, Transition), TzError> {
+        let start = self.transition().ok_or(TzError::InvalidRule)?;
+        if !self.eat(b',') {
+            return Err(TzError::InvalidRule);
+        }
+        let end = self.transition().ok_or(TzError::InvalidRule)?;
+
+        Ok((start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, s)
+            .unwrap()
+    }
+
+    const CET: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+    const SYDNEY: &str = "AEST-10AEDT,M10.1.0,M4.1.0/3";
+
+    #[test]
+    fn fixed_offsets() {
+        let cases = [
+            ("UTC0", 0),
+            ("IST-5:30", 19_800),
+            ("<+0545>-5:45", 20_700),
+            ("<-03>3", -10_800),
+            ("EST+5", -18_000),
+        ];
+
+        for (s, offset) in cases {
+            let tz = PosixTz::parse(s).unwrap();
+            assert_eq!(
+                tz.offset_secs(utc(2024, 7, 1, 0, 0, 0)),
+                offset,
+                "{s} offset"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [
+            ("", TzError::InvalidName),
+            ("C-1", TzError::InvalidName),
+            ("<+05>", TzError::InvalidOffset),
+            ("CET", TzError::InvalidOffset),
+            ("CET-25", TzError::InvalidOffset),
+            ("CET-1:60", TzError::InvalidOffset),
+            ("CET-1CEST,M13.5.0,M10.5.0", TzError::InvalidRule),
+            ("CET-1CEST,M3.6.0,M10.5.0", TzError::InvalidRule),
+            ("CET-1CEST,M3.5.0", TzError::InvalidRule),
+            ("CET-1CEST,J0,J100", TzError::InvalidRule),
+            ("CET-1CEST,M3.5.0,M10.5.0/3x", TzError::TrailingCharacters),
+        ];
+
+        for (s, err) in cases {
+            assert_eq!(PosixTz::parse(s), Err(err), "{s:?} is invalid");
+        }
+    }
+
+    #[test]
+    fn northern_transitions() {
+        let tz = PosixTz::parse(CET).unwrap();
+
+        // Last Sunday of March 2024 is the 31st, 02:00 CET is 01:00 UTC
+        assert_eq!(tz.offset_secs(utc(2024, 3, 31, 0, 59, 59)), 3600, "before");
+        assert_eq!(
+            tz.offset_secs(utc(2024, 3, 31, 1, 0, 0)),
+            7200,
+            "DST starts"
+        );
+
+        // Last Sunday of October 2024 is the 27th, 03:00 CEST is 01:00 UTC
+        assert_eq!(tz.offset_secs(utc(2024, 10, 27, 0, 59, 59)), 7200, "DST");
+        assert_eq!(tz.offset_secs(utc(2024, 10, 27, 1, 0, 0)), 3600, "DST ends");
+
+        assert_eq!(tz.offset_secs(utc(2024, 1, 15, 12, 0, 0)), 3600, "winter");
+        assert_eq!(
+            tz.offset_secs(utc(2024, 12, 31, 23, 0, 0)),
+            3600,
+            "new year"
+        );
+    }
+
+    #[test]
+    fn southern_transitions() {
+        let tz = PosixTz::parse(SYDNEY).unwrap();
+
+        // First Sunday of April 2024 is the 7th, 03:00 AEDT is 16:00 UTC the day before
+        assert_eq!(tz.offset_secs(utc(2024, 4, 6, 15, 59, 59)), 39_600, "DST");
+        assert_eq!(
+            tz.offset_secs(utc(2024, 4, 6, 16, 0, 0)),
+            36_000,
+            "DST ends"
+        );
+
+        // First Sunday of October 2024 is the 6th, 02:00 AEST is 16:00 UTC the day before
+        assert_eq!(
+            tz.offset_secs(utc(2024, 10, 5, 15, 59, 59)),
+            36_000,
+            "before"
+        );
+        assert_eq!(
+            tz.offset_secs(utc(2024, 10, 5, 16, 0, 0)),
+            39_600,
+            "DST starts"
+        );
+
+        assert_eq!(tz.offset_secs(utc(2024, 1, 1, 0, 0, 0)), 39_600, "summer");
+        assert_eq!(tz.offset_secs(utc(2024, 7, 1, 0, 0, 0)), 36_000, "winter");
+    }
+
+    #[test]
+    fn default_rules() {
+        let tz = PosixTz::parse("EST5EDT").unwrap();
+
+        // Second Sunday of March 2024 is the 10th, 02:00 EST is 07:00 UTC
+        assert_eq!(
+            tz.offset_secs(utc(2024, 3, 10, 6, 59, 59)),
+            -18_000,
+            "before"
+        );
+        assert_eq!(
+            tz.offset_secs(utc(2024, 3, 10, 7, 0, 0)),
+            -14_400,
+            "DST starts"
+        );
+
+        // First Sunday of November 2024 is the 3rd, 02:00 EDT is 06:00 UTC
+        assert_eq!(
+            tz.offset_secs(utc(2024, 11, 3, 6, 0, 0)),
+            -18_000,
+            "DST ends"
+        );
+    }
+
+    #[test]
+    fn julian_days() {
+        assert_eq!(
+            RuleDate::Julian(60).in_year(2024),
+            NaiveDate::from_ymd_opt(2024, 3, 1),
+            "J60 skips February 29"
+        );
+        assert_eq!(
+            RuleDate::ZeroBased(59).in_year(2024),
+            NaiveDate::from_ymd_opt(2024, 2, 29),
+            "59 counts February 29"
+        );
+        assert_eq!(
+            RuleDate::MonthWeekDay {
+                month: 2,
+                week: 5,
+                weekday: 4
+            }
+            .in_year(2024),
+            NaiveDate::from_ymd_opt(2024, 2, 29),
+            "week 5 is the last Thursday"
+        );
+    }
+
+    #[test]
+    fn local_to_utc() {
+        let tz = PosixTz::parse(CET).unwrap();
+
+        assert_eq!(
+            tz.to_utc(utc(2024, 1, 15, 7, 0, 0)),
+            utc(2024, 1, 15, 6, 0, 0),
+            "winter"
+        );
+        assert_eq!(
+            tz.to_utc(utc(2024, 7, 15, 7, 0, 0)),
+            utc(2024, 7, 15, 5, 0, 0),
+            "summer"
+        );
+        assert_eq!(
+            tz.to_utc(utc(2024, 3, 31, 2, 30, 0)),
+            utc(2024, 3, 31, 1, 30, 0),
+            "skipped time is moved forward to 03:30 CEST"
+        );
+        assert_eq!(
+            tz.to_utc(utc(2024, 10, 27, 2, 30, 0)),
+            utc(2024, 10, 27, 0, 30, 0),
+            "repeated time is the earlier 02:30 CEST"
+        );
+    }
+}
@@ -2,9 +2,16 @@
 //!
 //! Holds implementation details of the [`Buzzer`] struct.
 
-use esp_hal::ledc::{LowSpeed, channel::ChannelIFace as _};
+use esp_hal::ledc::{
+    LowSpeed,
+    channel::{ChannelHW as _, ChannelIFace as _},
+};
 
-use crate::buzzer::BUZZER_VOLUME;
+use super::curve;
+use crate::{
+    buzzer::BUZZER_VOLUME,
+    pwm::{BASE_FREQUENCY_HZ, DUTY_RANGE, set_timer0_frequency},
+};
 
 /// The [`Buzzer`] can only be `On` or `Off`.
 ///
@@ -19,6 +26,8 @@ pub(crate) struct Buzzer {
     output: esp_hal::ledc::channel::Channel<'static, LowSpeed>,
     volume: u8,
     state: BuzzerState,
+    /// The frequency of the tone being played, if any.
+    tone: Option<u32>,
 }
 
 impl Buzzer {
@@ -30,14 +39,52 @@ impl Buzzer {
             output,
             volume: 0,
             state: BuzzerState::Off,
+            tone: None,
         }
     }
 
     pub fn activate(&mut self) {
-        self.output.set_duty(self.volume).unwrap();
+        self.output.set_duty_hw(self.duty(self.volume));
         self.state = BuzzerState::On;
     }
 
+    /// Turns on the buzzer at `volume` without changing the set volume.
+    ///
+    /// Used to ramp up towards the set volume.
+    pub fn activate_at(&mut self, volume: u8) {
+        self.output.set_duty_hw(self.duty(volume.min(self.volume)));
+        self.state = BuzzerState::On;
+    }
+
+    /// The set volume.
+    #[inline]
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    /// Plays a tone at `hz` for a passive buzzer.
+    /// `None` restores the base PWM frequency.
+    pub fn set_tone(&mut self, hz: Option<u32>) {
+        if self.tone != hz {
+            set_timer0_frequency(hz.unwrap_or(BASE_FREQUENCY_HZ));
+            self.tone = hz;
+        }
+    }
+
+    /// Converts a volume to a duty out of [`DUTY_RANGE`] using the [volume curve](curve).
+    ///
+    /// A square wave is the loudest at 50% duty,
+    /// so the duty is capped at half while playing a tone.
+    #[inline]
+    fn duty(&self, volume: u8) -> u32 {
+        let duty = curve::duty(volume);
+        if self.tone.is_some() {
+            duty.min(DUTY_RANGE.div_euclid(2))
+        } else {
+            duty
+        }
+    }
+
     pub fn deactivate(&mut self) {
         self.output.set_duty(0).unwrap();
         self.state = BuzzerState::Off;
@@ -54,7 +101,8 @@ impl Buzzer {
         }
     }
 
-    /// Sets the duty cycle of the PWM signal.
+    /// Sets the volume as a percentage, which is mapped to
+    /// the duty cycle of the PWM signal when activated.
     ///
     /// # Panics
     /// Panics if `volume > 100`.
@@ -0,0 +1,93 @@
+//! # Volume Curve
+//! Maps the volume percentage to a 12-bit PWM duty so loudness scales evenly.
+//!
+//! Loudness barely changes at high duties, so a linear mapping crams the usable
+//! range into the first few percent. Instead the volume is raised to a power and
+//! offset by the quietest audible duty, calibrated per [`BuzzerModel`].
+
+use crate::pwm::DUTY_RANGE;
+
+/// The buzzer the volume curve is calibrated for.
+pub(crate) const BUZZER_MODEL: BuzzerModel = {
+    const MODEL: &str = option_env!("BUZZER_MODEL").unwrap_or("active");
+    BuzzerModel::parse(MODEL).expect("Failed to parse .env: BUZZER_MODEL")
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub(crate) enum BuzzerModel {
+    /// Buzzer with a built-in oscillator. Loudness follows the average power.
+    Active,
+    /// Magnetic buzzer driven by the PWM signal. Loudest at 50% duty.
+    Passive,
+    /// Piezo disc. Loudest at 50% duty and audible at very low duties.
+    Piezo,
+}
+
+/// Calibration of a volume curve.
+struct Curve {
+    /// Duty at 1% volume.
+    min: u32,
+    /// Duty at 100% volume.
+    max: u32,
+    exponent: u32,
+}
+
+impl BuzzerModel {
+    const fn parse(s: &str) -> Option<Self> {
+        if s.eq_ignore_ascii_case("active") {
+            Some(Self::Active)
+        } else if s.eq_ignore_ascii_case("passive") {
+            Some(Self::Passive)
+        } else if s.eq_ignore_ascii_case("piezo") {
+            Some(Self::Piezo)
+        } else {
+            None
+        }
+    }
+
+    const fn curve(self) -> Curve {
+        let half = DUTY_RANGE.div_euclid(2);
+
+        match self {
+            Self::Active => Curve {
+                min: 400,
+                max: DUTY_RANGE,
+                exponent: 2,
+            },
+            Self::Passive => Curve {
+                min: 40,
+                max: half,
+                exponent: 3,
+            },
+            Self::Piezo => Curve {
+                min: 8,
+                max: half,
+                exponent: 2,
+            },
+        }
+    }
+}
+
+/// Converts a volume percentage to a duty out of [`DUTY_RANGE`].
+///
+/// A volume of 0 is silent. Volumes above 100 are clamped.
+pub(super) fn duty(volume: u8) -> u32 {
+    if volume == 0 {
+        return 0;
+    }
+
+    let Curve { min, max, exponent } = BUZZER_MODEL.curve();
+    let span = u64::from(max.saturating_sub(min));
+
+    // Starts from 1% so it lands exactly on `min`
+    let progress = u64::from(volume.min(100).saturating_sub(1)).saturating_pow(exponent);
+    let scale = 99_u64.saturating_pow(exponent);
+
+    let duty = span
+        .saturating_mul(progress)
+        .checked_div(scale)
+        .unwrap_or_default();
+
+    // Cannot exceed `max`, which fits in a u32
+    u32::try_from(duty).unwrap_or(u32::MAX).saturating_add(min)
+}
@@ -0,0 +1,18 @@
+//! # Button Gestures
+//! Maps the gestures of the alarm button to what they do.
+//!
+//! Gestures are recognised by [`GestureDetector`], and each is mapped
+//! to a [`ButtonAction`] through [`BUTTON_MAP`].
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, rwlock::RwLock};
+
+pub(crate) use rusty_clock_core::buzzer::gesture::{
+    ButtonAction, ButtonMap, Edge, Gesture, GestureDetector,
+};
+
+/// What each gesture of the alarm button does.
+pub(crate) static BUTTON_MAP: RwLock<CriticalSectionRawMutex, ButtonMap> = RwLock::new(ButtonMap {
+    short: ButtonAction::Snooze,
+    long: ButtonAction::Dismiss,
+    double: ButtonAction::Backlight,
+});
@@ -0,0 +1,155 @@
+//! # Volume Knob
+//! Reads the volume from a potentiometer on an ADC1 pin.
+//!
+//! The knob and `POST /volume` both set the volume, so the most recent change wins.
+//! The knob only sends a new volume when it is turned past the hysteresis,
+//! so a volume set over the web stays until the knob is turned again.
+//! At boot the knob position overrides the default volume.
+
+use defmt::{debug, info};
+use embassy_time::Timer;
+use esp_hal::{
+    Async,
+    analog::adc::{Adc, AdcConfig, AdcPin, Attenuation},
+    peripherals::{ADC1, GPIO0, GPIO1, GPIO4},
+};
+
+use super::{BUZZER_COMMANDS, BuzzerAction};
+
+/// The GPIO the potentiometer is connected to. Disabled if not set.
+///
+/// Only GPIO0, GPIO1 and GPIO4 are free ADC1 pins.
+pub(crate) const POT_PIN: Option<u8> = match option_env!("POT_PIN") {
+    Some(pin) => Some(
+        u8::from_str_radix(pin, 10)
+            .ok()
+            .expect("Failed to parse .env: POT_PIN"),
+    ),
+    None => None,
+};
+
+// TEST: Pin is a free ADC1 pin
+static_assertions::const_assert!(matches!(POT_PIN, None | Some(0 | 1 | 4)));
+
+/// How often the knob is sampled.
+const SAMPLE_MS: u64 = 20;
+
+/// The highest ADC reading at 12-bit resolution.
+const ADC_MAX: u32 = 4095;
+
+/// Readings within this distance of either end snap to 0 or 100,
+/// since the ADC is not linear near its limits.
+const DEAD_ZONE: u32 = 100;
+
+/// How far the smoothed reading must move before the volume changes.
+/// About 2% of the range, so ADC noise does not flip between volumes.
+const HYSTERESIS: u32 = 80;
+
+/// Each sample contributes `1 / 2^SMOOTHING` to the moving average.
+const SMOOTHING: u32 = 3;
+
+/// A potentiometer on one of the ADC1 pins.
+pub(crate) enum KnobPin {
+    Gpio0(GPIO0<'static>),
+    Gpio1(GPIO1<'static>),
+    Gpio4(GPIO4<'static>),
+}
+
+/// A [`KnobPin`] set up for ADC readings.
+enum KnobChannel {
+    Gpio0(AdcPin<GPIO0<'static>, ADC1<'static>>),
+    Gpio1(AdcPin<GPIO1<'static>, ADC1<'static>>),
+    Gpio4(AdcPin<GPIO4<'static>, ADC1<'static>>),
+}
+
+impl KnobChannel {
+    async fn read(&mut self, adc: &mut Adc<'static, ADC1<'static>, Async>) -> u16 {
+        match self {
+            Self::Gpio0(pin) => adc.read_oneshot(pin).await,
+            Self::Gpio1(pin) => adc.read_oneshot(pin).await,
+            Self::Gpio4(pin) => adc.read_oneshot(pin).await,
+        }
+    }
+}
+
+/// Exponential moving average of the ADC readings with hysteresis.
+struct Smoother {
+    /// Average scaled by `2^SMOOTHING` to keep the fraction.
+    scaled: u32,
+    /// The average the volume was last sent for.
+    reported: Option<u32>,
+}
+
+impl Smoother {
+    fn new(first: u16) -> Self {
+        Self {
+            scaled: u32::from(first).wrapping_shl(SMOOTHING),
+            reported: None,
+        }
+    }
+
+    /// Adds a reading and returns the new volume if the knob has moved.
+    fn update(&mut self, reading: u16) -> Option<u8> {
+        // avg += (reading - avg) / 2^SMOOTHING
+        self.scaled = self
+            .scaled
+            .saturating_sub(self.scaled.wrapping_shr(SMOOTHING))
+            .saturating_add(reading.into());
+        let avg = self.scaled.wrapping_shr(SMOOTHING);
+
+        if self
+            .reported
+            .is_some_and(|reported| avg.abs_diff(reported) < HYSTERESIS)
+        {
+            return None;
+        }
+
+        self.reported = Some(avg);
+        Some(to_volume(avg))
+    }
+}
+
+/// Maps an ADC reading to a volume percentage.
+fn to_volume(reading: u32) -> u8 {
+    let span = ADC_MAX.saturating_sub(DEAD_ZONE.saturating_mul(2));
+    let reading = reading.saturating_sub(DEAD_ZONE).min(span);
+
+    let volume = reading
+        .saturating_mul(100)
+        .saturating_add(span.div_euclid(2))
+        .checked_div(span)
+        .unwrap_or_default();
+
+    // Cannot truncate since reading is clamped to span
+    volume.min(100).truncate()
+}
+
+#[embassy_executor::task]
+/// Samples the knob and sets the volume when it is turned.
+pub(super) async fn knob_task(adc: ADC1<'static>, pin: KnobPin) -> ! {
+    info!("[knob] Reading volume knob");
+
+    let mut config = AdcConfig::new();
+    let mut channel = match pin {
+        KnobPin::Gpio0(pin) => KnobChannel::Gpio0(config.enable_pin(pin, Attenuation::_11dB)),
+        KnobPin::Gpio1(pin) => KnobChannel::Gpio1(config.enable_pin(pin, Attenuation::_11dB)),
+        KnobPin::Gpio4(pin) => KnobChannel::Gpio4(config.enable_pin(pin, Attenuation::_11dB)),
+    };
+    let mut adc = Adc::new(adc, config).into_async();
+
+    let first = channel.read(&mut adc).await;
+    let mut smoother = Smoother::new(first);
+
+    loop {
+        let reading = channel.read(&mut adc).await;
+
+        if let Some(volume) = smoother.update(reading) {
+            debug!("[knob] Volume set to {}", volume);
+            BUZZER_COMMANDS
+                .send(BuzzerAction::SetVolume(volume).into())
+                .await;
+        }
+
+        Timer::after_millis(SAMPLE_MS).await;
+    }
+}
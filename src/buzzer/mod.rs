@@ -2,27 +2,61 @@
 //! This module holds all the logic regarding the buzzer.
 
 mod buzzer_struct;
+mod curve;
+pub(crate) mod gesture;
+mod knob;
+pub(crate) mod pattern;
+mod ramp;
+pub(crate) mod ring;
 mod task;
 
 pub(crate) use buzzer_struct::*;
+pub(crate) use knob::{KnobPin, POT_PIN};
+use pattern::Pattern;
+pub(crate) use ring::{RING_COMMANDS, RING_LOG, RING_STATE, RingAction};
 
 use embassy_executor::Spawner;
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
-use esp_hal::{gpio, ledc::LowSpeed};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    priority_channel::{Min, PriorityChannel},
+};
+use esp_hal::{gpio, ledc::LowSpeed, peripherals::ADC1};
 
+use crate::priority_command::{Discriminant, Priority};
+
+#[repr(u8)]
+/// Buzzer Actions.
+///
+/// Actions higher up take priority over the ones below.
 pub(crate) enum BuzzerAction {
-    On,
     Off,
+    On,
+    /// Plays a pattern on repeat until stopped.
+    Play {
+        pattern: Pattern,
+        /// Gradually raises the volume to the set volume.
+        ramp: bool,
+    },
+    /// Plays a tone in Hz on a passive buzzer until stopped.
+    Tone(u32),
     Toggle,
     SetVolume(u8),
 }
 
-/// Sets the buzzer signal and volume.
-pub(crate) static BUZZER_ACTION_SIGNAL: Signal<CriticalSectionRawMutex, BuzzerAction> =
-    Signal::new();
+// SAFETY: `BuzzerAction` is `#[repr(u8)]`.
+unsafe impl Discriminant for BuzzerAction {}
 
-/// Signal is used to set the timer in seconds.
-pub(crate) static TIMER_SIGNAL: Signal<CriticalSectionRawMutex, u32> = Signal::new();
+/// The inbox for all buzzer actions.
+///
+/// Actions are queued, so [`BuzzerAction::Off`] is never lost
+/// even if other actions are sent at the same time. Since it is handled first,
+/// it drops the actions queued before it that would turn the buzzer back on.
+pub(crate) static BUZZER_COMMANDS: PriorityChannel<
+    CriticalSectionRawMutex,
+    Priority<BuzzerAction>,
+    Min,
+    8,
+> = PriorityChannel::new();
 
 /// NOTE: ESP32-C3 does not natively support 8-bit atomics (rv32imc).\
 /// Hence we use `portable_atomic` since it supports [`fetch_not`](`portable_atomic::AtomicBool::fetch_not`).
@@ -33,11 +67,15 @@ pub(crate) static IS_BUZZER_ON: portable_atomic::AtomicBool =
 pub(crate) static BUZZER_VOLUME: portable_atomic::AtomicU8 = portable_atomic::AtomicU8::new(0);
 
 /// Initialize the buzzer and beep to signal readiness.
+///
+/// The volume knob is only read if `knob_pin` is set.
 pub(super) fn init(
     spawner: Spawner,
     output_channel: esp_hal::ledc::channel::Channel<'static, LowSpeed>,
     button_pin: gpio::AnyPin<'static>,
     alarm_pin: gpio::AnyPin<'static>,
+    adc: ADC1<'static>,
+    knob_pin: Option<KnobPin>,
 ) {
     let mut buzzer = Buzzer::new(output_channel);
     buzzer.set_volume(80);
@@ -45,5 +83,9 @@ pub(super) fn init(
     spawner.spawn(task::action_task(buzzer).unwrap());
     spawner.spawn(task::alarm_task(alarm_pin).unwrap());
     spawner.spawn(task::button_task(button_pin).unwrap());
-    spawner.spawn(task::timer_task().unwrap());
+    spawner.spawn(ring::ring_task().unwrap());
+
+    if let Some(pin) = knob_pin {
+        spawner.spawn(knob::knob_task(adc, pin).unwrap());
+    }
 }
@@ -0,0 +1,169 @@
+//! # Beep Patterns
+//! Plays the [`Pattern`]s the buzzer can play instead of a continuous tone.
+//!
+//! The duty of each [`Step`] is relative to the set volume,
+//! so patterns still respect the volume and the [`Ramp`].
+
+use embassy_time::{Duration, Instant};
+use rusty_clock_core::buzzer::{
+    pattern::{MELODY_GAP, Sound, Step},
+    rtttl::Melody,
+};
+
+use super::{
+    Buzzer,
+    ramp::{RAMP_STEP, Ramp},
+};
+
+pub(crate) use rusty_clock_core::buzzer::pattern::Pattern;
+
+/// Keeps track of the next [`Step`] to play.
+enum Cursor {
+    Steps {
+        steps: &'static [Step],
+        /// Index of the next step.
+        next: usize,
+    },
+    Melody {
+        /// The melody from the start, to repeat it.
+        start: Melody<'static>,
+        melody: Melody<'static>,
+    },
+}
+
+impl Cursor {
+    fn new(pattern: Pattern) -> Self {
+        match pattern.sound() {
+            Sound::Steps(steps) => Self::Steps { steps, next: 0 },
+            Sound::Melody(rtttl) => match Melody::parse(rtttl) {
+                Ok(melody) => {
+                    defmt::debug!("[buzzer] Playing {=str}", melody.name);
+                    Self::Melody {
+                        start: melody.clone(),
+                        melody,
+                    }
+                }
+                Err(err) => {
+                    defmt::error!("[buzzer] Invalid melody: {}", err);
+                    Self::new(Pattern::Continuous)
+                }
+            },
+        }
+    }
+
+    /// Whether the pattern only has a single repeating step.
+    #[inline]
+    fn is_steady(&self) -> bool {
+        matches!(self, Self::Steps { steps, .. } if steps.len() <= 1)
+    }
+
+    /// Returns the next step, starting over at the end of the pattern.
+    fn next_step(&mut self) -> Step {
+        match self {
+            Self::Steps { steps, next } => {
+                let step = steps.get(*next).copied().unwrap_or(MELODY_GAP);
+                *next = next.saturating_add(1);
+                if *next >= steps.len() {
+                    *next = 0;
+                }
+                step
+            }
+            Self::Melody { start, melody } => match melody.next() {
+                Some(Ok(note)) => note.into(),
+                Some(Err(err)) => {
+                    defmt::warn!("[buzzer] Skipping rest of melody: {}", err);
+                    *melody = start.clone();
+                    MELODY_GAP
+                }
+                None => {
+                    *melody = start.clone();
+                    MELODY_GAP
+                }
+            },
+        }
+    }
+}
+
+/// Plays a [`Pattern`] on repeat, optionally ramping up the volume.
+pub(super) struct Player {
+    cursor: Cursor,
+    step: Step,
+    step_ends: Instant,
+    ramp: Option<Ramp>,
+    last_update: Instant,
+}
+
+impl Player {
+    pub fn start(pattern: Pattern, ramp: Option<Ramp>, now: Instant) -> Self {
+        let mut cursor = Cursor::new(pattern);
+        let step = cursor.next_step();
+
+        Self {
+            cursor,
+            step,
+            step_ends: after(now, step.ms),
+            ramp,
+            last_update: now,
+        }
+    }
+
+    /// Ramps towards `volume` instead, if ramping.
+    #[inline]
+    pub fn set_volume(&mut self, volume: u8) {
+        if let Some(ramp) = self.ramp.as_mut() {
+            ramp.set_target(volume);
+        }
+    }
+
+    /// Returns when the output should be updated next.
+    ///
+    /// Returns `None` if the output no longer changes.
+    pub fn next_update(&self) -> Option<Instant> {
+        let step = (!self.cursor.is_steady()).then_some(self.step_ends);
+        let ramp = self.ramp.as_ref().map(|_| {
+            self.last_update
+                .checked_add(RAMP_STEP)
+                .unwrap_or(Instant::MAX)
+        });
+
+        match (step, ramp) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Advances the pattern to `now` and updates the output.
+    pub fn update(&mut self, output: &mut Buzzer, now: Instant) {
+        self.last_update = now;
+
+        while !self.cursor.is_steady() && now >= self.step_ends {
+            self.step = self.cursor.next_step();
+            self.step_ends = after(self.step_ends, self.step.ms);
+        }
+
+        if self.ramp.as_ref().is_some_and(|r| r.is_done(now)) {
+            self.ramp = None;
+        }
+        let volume = self
+            .ramp
+            .as_ref()
+            .map_or(output.volume(), |r| r.volume_at(now));
+
+        let scaled = u16::from(volume)
+            .saturating_mul(u16::from(self.step.duty))
+            .checked_div(100)
+            .unwrap_or_default();
+
+        output.set_tone(self.step.hz);
+        // Cannot truncate since both are percentages
+        output.activate_at(scaled.truncate());
+    }
+}
+
+/// Returns the instant `ms` milliseconds after `instant`.
+#[inline]
+fn after(instant: Instant, ms: u32) -> Instant {
+    instant
+        .checked_add(Duration::from_millis(ms.into()))
+        .unwrap_or(Instant::MAX)
+}
@@ -0,0 +1,77 @@
+//! # Volume Ramp
+//! Gradually raises the buzzer volume when an alarm goes off
+//! instead of starting at full blast.
+
+use embassy_time::{Duration, Instant};
+
+/// How long it takes to reach the set volume. A value of 0 disables the ramp.
+const RAMP_SECS: u64 = {
+    const SECS: &str = option_env!("RAMP_SECS").unwrap_or("30");
+    u64::from_str_radix(SECS, 10)
+        .ok()
+        .expect("Failed to parse .env: RAMP_SECS")
+};
+
+/// The volume the ramp starts at.
+const RAMP_START_VOLUME: u8 = {
+    const VOL: &str = option_env!("RAMP_START_VOLUME").unwrap_or("10");
+    u8::from_str_radix(VOL, 10)
+        .ok()
+        .expect("Failed to parse .env: RAMP_START_VOLUME")
+};
+
+// TEST: Volume is a percentage
+static_assertions::const_assert!(RAMP_START_VOLUME <= 100);
+
+/// How often the volume is raised during a ramp.
+pub(super) const RAMP_STEP: Duration = Duration::from_millis(250);
+
+/// Linearly raises the volume from [`RAMP_START_VOLUME`] to a target over [`RAMP_SECS`].
+pub(super) struct Ramp {
+    start: Instant,
+    target: u8,
+}
+
+impl Ramp {
+    /// Starts a ramp towards `target` now.
+    ///
+    /// Returns `None` if the ramp is disabled.
+    pub fn start(target: u8) -> Option<Self> {
+        (RAMP_SECS > 0).then(|| Self {
+            start: Instant::now(),
+            target,
+        })
+    }
+
+    /// Ramps towards `target` instead, keeping the progress made so far.
+    #[inline]
+    pub fn set_target(&mut self, target: u8) {
+        self.target = target;
+    }
+
+    /// Returns the volume at `now`.
+    ///
+    /// Starts at the target if it is below [`RAMP_START_VOLUME`].
+    pub fn volume_at(&self, now: Instant) -> u8 {
+        let from = RAMP_START_VOLUME.min(self.target);
+        let span = u64::from(self.target.saturating_sub(from));
+
+        let elapsed = now.saturating_duration_since(self.start).as_millis();
+        let total = RAMP_SECS.saturating_mul(1000);
+
+        let raised = span
+            .saturating_mul(elapsed)
+            .checked_div(total)
+            .unwrap_or(span)
+            .min(span);
+
+        // Cannot truncate since `raised <= span <= 100`
+        from.saturating_add(raised.truncate())
+    }
+
+    /// Whether the target volume has been reached at `now`.
+    #[inline]
+    pub fn is_done(&self, now: Instant) -> bool {
+        self.volume_at(now) >= self.target
+    }
+}
@@ -0,0 +1,377 @@
+//! # Ring Session
+//! Tracks a ringing alarm from the moment it goes off until it is dismissed.
+//!
+//! By default, a short press of the alarm button snoozes the alarm for [`SNOOZE_MINUTES`],
+//! after which it rings again. A long press dismisses it. Only [`MAX_SNOOZES`]
+//! snoozes are allowed per alarm, after which it can only be dismissed.
+//!
+//! If nobody answers within [`MAX_RING_SECS`], the alarm stops by itself and
+//! rings again after [`RERING_MINUTES`], up to [`RERING_COUNT`] times.
+//! Alarms that are never answered are recorded in [`RING_LOG`].
+//!
+//! An alarm or timer that goes off during a ring session rings once the session
+//! ends, with its own snoozes and re-rings.
+
+use core::fmt::Write as _;
+
+use chrono::{Timelike as _, Utc};
+use defmt::{info, warn};
+use embassy_futures::select::{Either, Either3, select, select3};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel, rwlock::RwLock,
+};
+use embassy_time::{Duration, Instant, Ticker, Timer};
+
+use super::{BUZZER_COMMANDS, BuzzerAction, pattern::Pattern};
+use crate::{
+    lcd::{LCD_COMMANDS, LcdAction, LcdDisplayString},
+    rtc_ds3231::{TIME_WATCH, rtc_time::RtcDateTime},
+};
+
+/// How long a snooze lasts.
+pub(crate) const SNOOZE_MINUTES: u64 = {
+    const MINS: &str = option_env!("SNOOZE_MINUTES").unwrap_or("9");
+    u64::from_str_radix(MINS, 10)
+        .ok()
+        .expect("Failed to parse .env: SNOOZE_MINUTES")
+};
+
+// TEST: A zero minute snooze would ring again immediately
+static_assertions::const_assert!(SNOOZE_MINUTES > 0);
+
+/// How many times a single alarm can be snoozed.
+pub(crate) const MAX_SNOOZES: u8 = {
+    const COUNT: &str = option_env!("MAX_SNOOZES").unwrap_or("3");
+    u8::from_str_radix(COUNT, 10)
+        .ok()
+        .expect("Failed to parse .env: MAX_SNOOZES")
+};
+
+/// How long an alarm rings before it stops by itself.
+/// A value of 0 rings until dismissed.
+///
+/// Defaults to 5 seconds in debug builds to stop it from bleeding my ears while devving.
+const MAX_RING_SECS: u64 = {
+    const DEFAULT: &str = if cfg!(debug_assertions) { "5" } else { "300" };
+    const SECS: &str = option_env!("MAX_RING_SECS").unwrap_or(DEFAULT);
+    u64::from_str_radix(SECS, 10)
+        .ok()
+        .expect("Failed to parse .env: MAX_RING_SECS")
+};
+
+/// How many times an alarm that stopped by itself rings again.
+const RERING_COUNT: u8 = {
+    const COUNT: &str = option_env!("RERING_COUNT").unwrap_or("2");
+    u8::from_str_radix(COUNT, 10)
+        .ok()
+        .expect("Failed to parse .env: RERING_COUNT")
+};
+
+/// How long to wait before ringing again after an alarm stopped by itself.
+const RERING_MINUTES: u64 = {
+    const MINS: &str = option_env!("RERING_MINUTES").unwrap_or("10");
+    u64::from_str_radix(MINS, 10)
+        .ok()
+        .expect("Failed to parse .env: RERING_MINUTES")
+};
+
+// TEST: A zero minute wait would ring again immediately
+static_assertions::const_assert!(RERING_MINUTES > 0);
+
+/// The maximum number of events held by [`RING_LOG`].
+const MAX_RING_EVENTS: usize = 8;
+
+/// The maximum number of alarms and timers waiting for the current ring session to end.
+const MAX_PENDING_RINGS: usize = 4;
+
+/// Alarms and timers that ring after the current ring session, oldest first.
+type PendingRings = heapless::Deque<RingSource, MAX_PENDING_RINGS>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+/// What started the ring session.
+pub(crate) enum RingSource {
+    Alarm,
+    Timer,
+}
+
+pub(crate) enum RingAction {
+    /// An alarm or timer went off.
+    Start(RingSource),
+    /// Silences the alarm and rings again after [`SNOOZE_MINUTES`].
+    Snooze,
+    /// Silences the alarm for good.
+    Dismiss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub(crate) enum RingState {
+    Idle,
+    Ringing {
+        source: RingSource,
+        /// Number of times the alarm has been snoozed so far.
+        snoozes: u8,
+    },
+    Snoozed {
+        source: RingSource,
+        snoozes: u8,
+        /// When the alarm rings again.
+        until: Instant,
+    },
+    /// Stopped by itself and waiting to ring again.
+    Rering {
+        source: RingSource,
+        /// Number of times the alarm has stopped by itself so far.
+        rerings: u8,
+        /// When the alarm rings again.
+        until: Instant,
+    },
+}
+
+impl RingState {
+    /// Whether a [`RingAction::Snooze`] would snooze the alarm.
+    #[inline]
+    pub fn can_snooze(&self) -> bool {
+        matches!(self, Self::Ringing { snoozes, .. } if *snoozes < MAX_SNOOZES)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[expect(dead_code, reason = "Read through Debug by /alarm/unanswered")]
+/// An alarm that stopped by itself without ever being answered.
+pub(crate) struct UnansweredRing {
+    pub source: RingSource,
+    /// When the alarm finally stopped. `None` if the time was not available.
+    pub at: Option<RtcDateTime<Utc>>,
+    /// How many times the alarm rang. 0 if too many were waiting to ring.
+    pub rings: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// The [`Pattern`] played for each [`RingSource`].
+pub(crate) struct RingPatterns {
+    pub alarm: Pattern,
+    pub timer: Pattern,
+}
+
+impl RingPatterns {
+    #[inline]
+    pub fn get(self, source: RingSource) -> Pattern {
+        match source {
+            RingSource::Alarm => self.alarm,
+            RingSource::Timer => self.timer,
+        }
+    }
+
+    #[inline]
+    pub fn set(&mut self, source: RingSource, pattern: Pattern) {
+        match source {
+            RingSource::Alarm => self.alarm = pattern,
+            RingSource::Timer => self.timer = pattern,
+        }
+    }
+}
+
+/// The patterns played when an alarm or timer goes off.
+pub(crate) static RING_PATTERNS: RwLock<CriticalSectionRawMutex, RingPatterns> =
+    RwLock::new(RingPatterns {
+        alarm: Pattern::Pulse,
+        timer: Pattern::TripleBeep,
+    });
+
+/// The inbox for the ring session.
+///
+/// Actions are queued in order, so a [`RingAction::Dismiss`] is never
+/// overwritten by an alarm or timer going off at the same time.
+pub(crate) static RING_COMMANDS: Channel<CriticalSectionRawMutex, RingAction, 8> = Channel::new();
+
+/// The current state of the ring session.
+pub(crate) static RING_STATE: RwLock<CriticalSectionRawMutex, RingState> =
+    RwLock::new(RingState::Idle);
+
+/// The last [`MAX_RING_EVENTS`] alarms that were never answered, oldest first.
+pub(crate) static RING_LOG: RwLock<
+    CriticalSectionRawMutex,
+    heapless::Deque<UnansweredRing, MAX_RING_EVENTS>,
+> = RwLock::new(heapless::Deque::new());
+
+#[embassy_executor::task]
+/// Listens for [`RING_COMMANDS`] and drives the buzzer through ring sessions.
+pub(super) async fn ring_task() -> ! {
+    let mut pending = PendingRings::new();
+
+    loop {
+        let action = match pending.pop_front() {
+            Some(source) => RingAction::Start(source),
+            None => RING_COMMANDS.receive().await,
+        };
+
+        match action {
+            RingAction::Start(source) => ring_session(source, &mut pending).await,
+            // Nothing is ringing. Acts as a plain stop button for
+            // anything else that turned on the buzzer.
+            RingAction::Snooze | RingAction::Dismiss => stop().await,
+        }
+    }
+}
+
+/// Rings until the alarm is dismissed or stops by itself, snoozing in between.
+///
+/// Alarms and timers that go off in the meantime are added to `pending`.
+async fn ring_session(source: RingSource, pending: &mut PendingRings) {
+    let mut snoozes = 0_u8;
+    let mut rerings = 0_u8;
+
+    loop {
+        info!("[ring] {} ringing. Snoozed {} times", source, snoozes);
+        *RING_STATE.write().await = RingState::Ringing { source, snoozes };
+        let pattern = RING_PATTERNS.read().await.get(source);
+        BUZZER_COMMANDS
+            .send(
+                BuzzerAction::Play {
+                    pattern,
+                    ramp: true,
+                }
+                .into(),
+            )
+            .await;
+        LCD_COMMANDS.signal(LcdAction::ClearStatus);
+
+        let deadline = (MAX_RING_SECS > 0).then(|| after(Duration::from_secs(MAX_RING_SECS)));
+
+        let (state, until) = loop {
+            // `None` if nobody answered in time
+            let action = match deadline {
+                Some(at) => match select(RING_COMMANDS.receive(), Timer::at(at)).await {
+                    Either::First(action) => Some(action),
+                    Either::Second(()) => None,
+                },
+                None => Some(RING_COMMANDS.receive().await),
+            };
+
+            match action {
+                Some(RingAction::Start(next)) => queue(pending, next).await,
+                Some(RingAction::Dismiss) => return stop().await,
+                Some(RingAction::Snooze) if snoozes < MAX_SNOOZES => {
+                    snoozes = snoozes.saturating_add(1);
+                    info!("[ring] Snoozed for {} minutes", SNOOZE_MINUTES);
+
+                    let until = after(Duration::from_secs(SNOOZE_MINUTES.saturating_mul(60)));
+                    break (
+                        RingState::Snoozed {
+                            source,
+                            snoozes,
+                            until,
+                        },
+                        until,
+                    );
+                }
+                Some(RingAction::Snooze) => warn!("[ring] Snooze limit reached. Dismiss to stop."),
+                None if rerings < RERING_COUNT => {
+                    rerings = rerings.saturating_add(1);
+                    info!(
+                        "[ring] Unanswered. Ringing again in {} minutes",
+                        RERING_MINUTES
+                    );
+
+                    let until = after(Duration::from_secs(RERING_MINUTES.saturating_mul(60)));
+                    break (
+                        RingState::Rering {
+                            source,
+                            rerings,
+                            until,
+                        },
+                        until,
+                    );
+                }
+                None => {
+                    warn!("[ring] Unanswered. Giving up.");
+                    stop().await;
+                    return log_unanswered(source, rerings.saturating_add(1)).await;
+                }
+            }
+        };
+
+        BUZZER_COMMANDS.send(BuzzerAction::Off.into()).await;
+        *RING_STATE.write().await = state;
+
+        let mut ticker = Ticker::every(Duration::from_secs(1));
+        loop {
+            show_countdown(state);
+
+            match select3(Timer::at(until), ticker.next(), RING_COMMANDS.receive()).await {
+                Either3::First(()) => break,
+                Either3::Second(()) | Either3::Third(RingAction::Snooze) => {}
+                Either3::Third(RingAction::Start(next)) => queue(pending, next).await,
+                Either3::Third(RingAction::Dismiss) => return stop().await,
+            }
+        }
+    }
+}
+
+/// Rings `source` once the current ring session ends.
+///
+/// Recorded in [`RING_LOG`] instead if too many are already waiting.
+async fn queue(pending: &mut PendingRings, source: RingSource) {
+    info!("[ring] {} went off while ringing. Ringing it next.", source);
+
+    if pending.push_back(source).is_err() {
+        warn!("[ring] Too many rings waiting. {} not rung.", source);
+        log_unanswered(source, 0).await;
+    }
+}
+
+/// Returns the instant `duration` from now.
+#[inline]
+fn after(duration: Duration) -> Instant {
+    Instant::now()
+        .checked_add(duration)
+        .expect("Instant should not overflow")
+}
+
+/// Turns off the buzzer and ends the ring session.
+async fn stop() {
+    BUZZER_COMMANDS.send(BuzzerAction::Off.into()).await;
+    // Also acknowledges messages such as missed alarms
+    LCD_COMMANDS.signal(LcdAction::ClearStatus);
+    *RING_STATE.write().await = RingState::Idle;
+}
+
+/// Records an alarm that was never answered and reports it on the LCD.
+async fn log_unanswered(source: RingSource, rings: u8) {
+    let at = TIME_WATCH.anon_receiver().try_get();
+
+    let mut s = LcdDisplayString::new();
+    let _ = match at {
+        Some(at) => {
+            let local = at.local();
+            write!(s, "Unanswered {:02}:{:02}", local.hour(), local.minute())
+        }
+        None => s.write_str("Unanswered alarm"),
+    };
+    LCD_COMMANDS.signal(LcdAction::Status(s));
+
+    let mut log = RING_LOG.write().await;
+    if log.is_full() {
+        log.pop_front();
+    }
+    // Cannot overflow since the oldest event was just removed if full
+    let _ = log.push_back(UnansweredRing { source, at, rings });
+}
+
+/// Shows the time left until the alarm rings again on the LCD.
+fn show_countdown(state: RingState) {
+    let (label, count, max, until) = match state {
+        RingState::Snoozed { snoozes, until, .. } => ("Snooze", snoozes, MAX_SNOOZES, until),
+        RingState::Rering { rerings, until, .. } => ("Rering", rerings, RERING_COUNT, until),
+        // Not waiting to ring again
+        RingState::Idle | RingState::Ringing { .. } => return,
+    };
+
+    let secs = until.saturating_duration_since(Instant::now()).as_secs();
+    let (mins, secs) = (secs.div_euclid(60), secs.rem_euclid(60));
+
+    let mut s = LcdDisplayString::new();
+    // Only truncated with an unreasonably long wait
+    let _ = write!(s, "{label} {mins}:{secs:02} {count}/{max}");
+    LCD_COMMANDS.signal(LcdAction::Status(s));
+}
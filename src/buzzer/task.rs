@@ -1,80 +1,189 @@
-use crate::buzzer::Buzzer;
+use crate::{
+    buzzer::Buzzer,
+    lcd::{LCD_COMMANDS, LcdAction},
+    menu::{MENU_INPUTS, MENU_OPEN, MenuInput},
+    rtc_ds3231::{ALARM_FIRED_SIGNAL, RTC_COMMANDS, RtcCommand},
+    timer::{TIMERS, TIMERS_CHANGED},
+};
 
-use super::{BUZZER_ACTION_SIGNAL, BuzzerAction, IS_BUZZER_ON, TIMER_SIGNAL};
-use defmt::{debug, info};
-use embassy_time::Timer;
+use super::{
+    BUZZER_COMMANDS, BuzzerAction, IS_BUZZER_ON, RING_COMMANDS, RING_STATE, RingAction,
+    gesture::{BUTTON_MAP, ButtonAction, Edge, Gesture, GestureDetector},
+    pattern::Player,
+    ramp::Ramp,
+    ring::{RingSource, RingState},
+};
+use defmt::{debug, info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_time::{Instant, Timer};
 use esp_hal::gpio::{AnyPin, Input, InputConfig, Pull};
 
 #[embassy_executor::task]
-/// This task listens for [`BUZZER_ACTION_SIGNAL`] and sets buzzer to
+/// This task listens for [`BUZZER_COMMANDS`] and sets buzzer to
 /// the appropriate action.
 ///
 /// This task takes ownership of [`Buzzer`] as opposed
 /// to wrapping it in a [`Mutex`](`embassy_sync::mutex::Mutex`) to share it between tasks.
 ///
-/// # Issues
-/// - An action may be skipped if its corresponding signal is
-/// written to again before the task is completed.
-/// - An [`::embassy_sync::channel::Channel`] could be used to queue up
-/// actions at the cost of higher RAM usage.
+/// [`BuzzerAction::Play`] plays a [`Pattern`](super::pattern::Pattern) on repeat,
+/// updating the output between steps and while ramping up the volume.
+/// Melodies and [`BuzzerAction::Tone`] change the PWM frequency, which is
+/// restored by [`BuzzerAction::Off`], [`BuzzerAction::On`] and [`BuzzerAction::Toggle`].
+/// Any other action besides [`BuzzerAction::SetVolume`] stops the pattern.
+///
+/// [`BuzzerAction::Off`] also drops any actions still queued, except for
+/// [`BuzzerAction::SetVolume`] which is applied.
 pub(super) async fn action_task(mut output: Buzzer) -> ! {
     // Test the buzzer.
     output.activate();
     Timer::after_millis(500).await;
     output.deactivate();
 
+    let cmd_rx = BUZZER_COMMANDS.receiver();
+    let mut player: Option<Player> = None;
+
     loop {
-        let action = BUZZER_ACTION_SIGNAL.wait().await;
+        let next_update = player.as_ref().and_then(Player::next_update);
+
+        let action = match next_update {
+            Some(at) => match select(cmd_rx.receive(), Timer::at(at)).await {
+                Either::First(action) => action.into_inner(),
+                Either::Second(()) => {
+                    if let Some(p) = player.as_mut() {
+                        p.update(&mut output, Instant::now());
+                    }
+                    continue;
+                }
+            },
+            None => cmd_rx.receive().await.into_inner(),
+        };
 
         match action {
+            BuzzerAction::Off => {
+                player = None;
+                output.deactivate();
+                output.set_tone(None);
+                IS_BUZZER_ON.store(false, core::sync::atomic::Ordering::Release);
+
+                // Off overtakes older actions in the queue, which would turn the buzzer back on
+                while let Ok(queued) = cmd_rx.try_receive() {
+                    if let BuzzerAction::SetVolume(vol) = queued.into_inner() {
+                        output.set_volume(vol);
+                    }
+                }
+            }
             BuzzerAction::On => {
+                player = None;
+                output.set_tone(None);
                 output.activate();
                 IS_BUZZER_ON.store(true, core::sync::atomic::Ordering::Release);
             }
-            BuzzerAction::Off => {
-                output.deactivate();
-                IS_BUZZER_ON.store(false, core::sync::atomic::Ordering::Release);
+            BuzzerAction::Play { pattern, ramp } => {
+                let ramp = if ramp {
+                    Ramp::start(output.volume())
+                } else {
+                    None
+                };
+
+                let mut p = Player::start(pattern, ramp, Instant::now());
+                p.update(&mut output, Instant::now());
+                player = Some(p);
+                IS_BUZZER_ON.store(true, core::sync::atomic::Ordering::Release);
+            }
+            BuzzerAction::Tone(hz) => {
+                player = None;
+                output.set_tone(Some(hz));
+                output.activate();
+                IS_BUZZER_ON.store(true, core::sync::atomic::Ordering::Release);
             }
             BuzzerAction::Toggle => {
+                player = None;
+                output.set_tone(None);
                 output.toggle();
                 IS_BUZZER_ON.fetch_not(core::sync::atomic::Ordering::AcqRel);
             }
-            BuzzerAction::SetVolume(vol) => output.set_volume(vol),
+            BuzzerAction::SetVolume(vol) => {
+                output.set_volume(vol);
+                // Keep ramping towards the new volume
+                if let Some(p) = player.as_mut() {
+                    p.set_volume(vol);
+                }
+            }
         }
     }
 }
 
 #[embassy_executor::task]
-/// Listens for [`TIMER_SIGNAL`] and sets timer accordingly.
-pub(super) async fn timer_task() -> ! {
-    info!("[buzzer:listen_for_timer] Listening for timer");
+/// Listens for gestures of the alarm button and runs the
+/// [`ButtonAction`] mapped to them in [`BUTTON_MAP`].
+pub(super) async fn button_task(input_pin: AnyPin<'static>) -> ! {
+    let mut input = Input::new(input_pin, InputConfig::default().with_pull(Pull::Up));
+    let mut detector = GestureDetector::new();
 
     loop {
-        let secs = TIMER_SIGNAL.wait().await;
+        let gesture = if let Some(at) = detector.deadline() {
+            match select(input.wait_for_any_edge(), Timer::at(at)).await {
+                Either::First(()) => detector.edge(button_edge(&input), Instant::now()),
+                Either::Second(()) => detector.timeout(Instant::now()),
+            }
+        } else {
+            input.wait_for_any_edge().await;
+            detector.edge(button_edge(&input), Instant::now())
+        };
 
-        Timer::after_secs(secs.into()).await;
-        BUZZER_ACTION_SIGNAL.signal(BuzzerAction::On);
+        if let Some(gesture) = gesture {
+            if let Some(input) = menu_input(gesture).await {
+                MENU_INPUTS.send(input).await;
+                continue;
+            }
 
-        // WARNING: Could potentially turn off the prematurely buzzer if
-        // an alarm goes off between the interval of waiting
-        Timer::after_secs(30).await;
-        BUZZER_ACTION_SIGNAL.signal(BuzzerAction::Off);
+            let action = BUTTON_MAP.read().await.get(gesture);
+            debug!("Alarm Button {}: {}", gesture, action);
+            run_button_action(action).await;
+        }
     }
 }
 
-#[embassy_executor::task]
-/// Listens for a button press which sets buzzer low.
-pub(super) async fn button_task(input_pin: AnyPin<'static>) -> ! {
-    let mut input = Input::new(input_pin, InputConfig::default().with_pull(Pull::Up));
+/// Returns what `gesture` does in the menu if it is open.
+///
+/// A ringing alarm takes precedence, so it can always be snoozed or dismissed.
+async fn menu_input(gesture: Gesture) -> Option<MenuInput> {
+    if !MENU_OPEN.load(core::sync::atomic::Ordering::Acquire)
+        || matches!(*RING_STATE.read().await, RingState::Ringing { .. })
+    {
+        return None;
+    }
 
-    loop {
-        #[cfg(debug_assertions)]
-        debug!("Waiting for alarm button press");
-        input.wait_for_falling_edge().await;
+    match gesture {
+        Gesture::Short => Some(MenuInput::Select),
+        Gesture::Long => Some(MenuInput::Back),
+        Gesture::Double => None,
+    }
+}
 
-        debug!("Alarm Button Pressed!");
-        BUZZER_ACTION_SIGNAL.signal(BuzzerAction::Off);
-        Timer::after_millis(500).await;
+/// The button is pulled up, so it is pressed while low.
+#[inline]
+fn button_edge(input: &Input<'_>) -> Edge {
+    if input.is_low() {
+        Edge::Press
+    } else {
+        Edge::Release
+    }
+}
+
+async fn run_button_action(action: ButtonAction) {
+    match action {
+        ButtonAction::Ignore => {}
+        ButtonAction::Snooze => RING_COMMANDS.send(RingAction::Snooze).await,
+        ButtonAction::Dismiss => RING_COMMANDS.send(RingAction::Dismiss).await,
+        ButtonAction::Backlight => LCD_COMMANDS.signal(LcdAction::BacklightToggle),
+        ButtonAction::RestartTimer => {
+            match TIMERS.write().await.restart_last(Instant::now()) {
+                Ok(id) => info!("[timer] Restarted last timer as {}", id),
+                Err(e) => warn!("[timer] Failed to restart last timer: {}", e),
+            }
+            TIMERS_CHANGED.signal(());
+        }
     }
 }
 
@@ -90,14 +199,21 @@ pub(super) async fn alarm_task(alarm_pin: AnyPin<'static>) -> ! {
         alarm_input.wait_for_falling_edge().await;
 
         info!("DS3231 Interrupt Received!");
-        BUZZER_ACTION_SIGNAL.signal(BuzzerAction::On);
+        RTC_COMMANDS.send(RtcCommand::AlarmFired.into()).await;
 
-        #[cfg(debug_assertions)]
-        {
-            // Stop it from bleeding my ears while devving
-            Timer::after_secs(5).await;
-            BUZZER_ACTION_SIGNAL.signal(BuzzerAction::Off);
-            info!("Buzzer set low");
+        let fired = ALARM_FIRED_SIGNAL.wait().await;
+        if fired.alarm1 {
+            info!("Alarm1 Fired!");
+        }
+        if fired.alarm2 {
+            info!("Alarm2 Fired!");
         }
+        if !fired.any() {
+            continue;
+        }
+
+        RING_COMMANDS
+            .send(RingAction::Start(RingSource::Alarm))
+            .await;
     }
 }
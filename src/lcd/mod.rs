@@ -29,11 +29,24 @@ pub(crate) enum LcdAction {
     BacklightToggle,
     Display(LcdDisplayString),
     DisplayLines(LcdDisplayString, LcdDisplayString),
+    /// Shows a status message on the bottom line in place of the date.
+    Status(LcdDisplayString),
+    /// Clears the status message and shows the date again.
+    ClearStatus,
 }
 
 /// The inbox for any LCD Display actions.
 pub(crate) static LCD_COMMANDS: Signal<CriticalSectionRawMutex, LcdAction> = Signal::new();
 
+/// The top and bottom lines of the menu.
+pub(crate) type MenuFrame = (LcdDisplayString, LcdDisplayString);
+
+/// The menu shown in place of the time, or [`None`] to show the time again.
+///
+/// Kept apart from [`LCD_COMMANDS`] so status updates sent while the menu
+/// is open cannot overwrite it. Only the latest frame is drawn.
+pub(crate) static LCD_MENU: Signal<CriticalSectionRawMutex, Option<MenuFrame>> = Signal::new();
+
 pub fn init(spawner: Spawner, i2c: I2cBus) {
     let hw = LcdHardware::new(PcAsync::new(i2c, SlaveAddr::Alternative(true, true, true)));
     let display: LcdDisplay = lcd::Display::new(hw);
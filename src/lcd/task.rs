@@ -1,7 +1,10 @@
-use super::{LCD_COMMANDS, LcdAction, LcdDisplay, print_lines};
-use crate::rtc_ds3231::{TIME_WATCH, rtc_time::RtcDateTime};
+use super::{LCD_COMMANDS, LCD_MENU, LcdAction, LcdDisplay, LcdDisplayString, print_lines};
+use crate::{
+    rtc_ds3231::{TIME_VALID, TIME_WATCH, rtc_time::RtcDateTime},
+    timer,
+};
 use chrono::Utc;
-use embassy_futures::select::{Either, select};
+use embassy_futures::select::{Either3, select3};
 use lcd::Backlight as _;
 
 const LCD_INITIAL: bool = {
@@ -12,26 +15,55 @@ const LCD_INITIAL: bool = {
 pub(crate) static BACKLIGHT_STATUS: portable_atomic::AtomicBool =
     portable_atomic::AtomicBool::new(LCD_INITIAL);
 
+/// Shown on the bottom line while the RTC time is not valid.
+const TIME_LOST_STR: &str = "Time lost! Sync";
+
 #[embassy_executor::task]
 pub(super) async fn runner_task(mut display: LcdDisplay) -> ! {
     init_display(&mut display).await;
     let mut rx = TIME_WATCH.receiver().unwrap();
-    let mut cached_date_str: heapless::String<11> = heapless::String::new();
+    let mut cached_bottom_str = LcdDisplayString::new();
+    let mut status: Option<LcdDisplayString> = None;
+    let mut menu_open = false;
 
     loop {
-        let action = select(rx.changed(), LCD_COMMANDS.wait()).await;
+        let action = select3(rx.changed(), LCD_COMMANDS.wait(), LCD_MENU.wait()).await;
 
         match action {
-            Either::First(time) => time_handle(&mut display, time, &mut cached_date_str).await,
-            Either::Second(action) => action_handle(&mut display, action).await,
+            // The menu takes over the whole display
+            Either3::First(_) if menu_open => {}
+            Either3::First(time) => {
+                time_handle(
+                    &mut display,
+                    time,
+                    &mut cached_bottom_str,
+                    status.as_deref(),
+                )
+                .await;
+            }
+            Either3::Second(action) => action_handle(&mut display, action, &mut status).await,
+            Either3::Third(Some((s1, s2))) => {
+                menu_open = true;
+                print_lines(&mut display, s1.as_str(), s2.as_str()).await;
+            }
+            Either3::Third(None) => {
+                menu_open = false;
+                // Forces both lines to be redrawn on the next tick
+                cached_bottom_str.clear();
+            }
         }
     }
 }
 
+/// Prints the time and the soonest timer countdown on the top line and
+/// the date, or `status` if set, on the bottom line.
+///
+/// While [`TIME_VALID`] is cleared, the bottom line warns that the time was lost instead.
 async fn time_handle(
     display: &mut LcdDisplay,
     datetime: RtcDateTime<Utc>,
-    cached_date_str: &mut heapless::String<11>,
+    cached_bottom_str: &mut LcdDisplayString,
+    status: Option<&str>,
 ) {
     let s = datetime.local().to_human_short();
     defmt::debug_assert!(s.is_ascii(), "Must be ASCII or CP437 to slice properly");
@@ -42,18 +74,37 @@ async fn time_handle(
     // Trims off the separator bar
     let date_str = &date_str[2..];
 
-    // If date is same as cached, print only the time. This avoids stutter
-    if date_str == cached_date_str {
+    // Derived on every tick, so a status being cleared cannot hide it
+    let bottom_str = if TIME_VALID.load(core::sync::atomic::Ordering::Acquire) {
+        status.unwrap_or(date_str)
+    } else {
+        TIME_LOST_STR
+    };
+
+    // Right-aligns the countdown in the 16 columns and pads the line to overwrite a previous one
+    let countdown = timer::soonest_countdown().await.unwrap_or_default();
+    let top_str: LcdDisplayString = heapless::format!(
+        "{time_str}{countdown:>width$}",
+        width = timer::MAX_COUNTDOWN_LEN
+    )
+    .unwrap();
+
+    // If bottom line is same as cached, print only the top line. This avoids stutter
+    if bottom_str == cached_bottom_str {
         display.home().await;
-        display.print(time_str).await;
+        display.print(&top_str).await;
     } else {
-        cached_date_str.clear();
-        cached_date_str.push_str(date_str).unwrap();
-        print_lines(display, time_str, date_str).await;
+        cached_bottom_str.clear();
+        cached_bottom_str.push_str(bottom_str).unwrap();
+        print_lines(display, &top_str, bottom_str).await;
     }
 }
 
-async fn action_handle(display: &mut LcdDisplay, action: LcdAction) {
+async fn action_handle(
+    display: &mut LcdDisplay,
+    action: LcdAction,
+    status: &mut Option<LcdDisplayString>,
+) {
     match action {
         LcdAction::BacklightOn => {
             BACKLIGHT_STATUS.store(true, core::sync::atomic::Ordering::Release);
@@ -71,6 +122,8 @@ async fn action_handle(display: &mut LcdDisplay, action: LcdAction) {
             display.print(s.as_str()).await;
         }
         LcdAction::DisplayLines(s1, s2) => print_lines(display, s1.as_str(), s2.as_str()).await,
+        LcdAction::Status(s) => *status = Some(s),
+        LcdAction::ClearStatus => *status = None,
     }
 }
 
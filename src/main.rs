@@ -7,7 +7,7 @@
 
 #![no_std]
 #![no_main]
-#![recursion_limit = "256"]
+#![recursion_limit = "512"]
 #![feature(
     decl_macro,
     strict_provenance_lints,
@@ -18,7 +18,6 @@
     const_trait_impl,
     const_option_ops,
     const_index,
-    const_convert,
     integer_widen_truncate,
 )]
 // Clippy Lints
@@ -112,9 +111,11 @@ extern crate alloc;
 mod buzzer;
 mod i2c;
 mod lcd;
+mod menu;
 mod priority_command;
 mod pwm;
 mod rtc_ds3231;
+mod timer;
 mod utils;
 mod wireless;
 
@@ -131,7 +132,8 @@ use panic_rtt_target as _;
 
 use crate::pwm::Channels;
 
-// NOTE: Using TZ_OFFSET since IANA Timezones adds unnecessary weight
+// NOTE: Using TZ_OFFSET since IANA Timezones adds unnecessary weight.
+// It is the fallback when no `TZ_RULE` string is set, see `rtc_ds3231::tz`.
 // PERF: Faster and leaner than LazyLock if you're
 // okay with using nightly features
 pub(crate) const TZ_OFFSET: i8 = {
@@ -207,12 +209,31 @@ async fn main(spawner: Spawner) {
     let Channels { channel0 } = pwm::init(peripherals.LEDC);
     let chan0 = channel0.with_output(output);
 
+    let knob_pin = match buzzer::POT_PIN {
+        Some(0) => Some(buzzer::KnobPin::Gpio0(peripherals.GPIO0)),
+        Some(1) => Some(buzzer::KnobPin::Gpio1(peripherals.GPIO1)),
+        Some(4) => Some(buzzer::KnobPin::Gpio4(peripherals.GPIO4)),
+        _ => None,
+    };
+
     info!("Init Buzzer...");
     buzzer::init(
         spawner,
         chan0,
         peripherals.GPIO7.degrade(),
         peripherals.GPIO6.degrade(),
+        peripherals.ADC1,
+        knob_pin,
+    );
+
+    info!("Init Timers...");
+    timer::init(spawner);
+
+    info!("Init Menu...");
+    menu::init(
+        spawner,
+        peripherals.GPIO10.degrade(),
+        peripherals.GPIO20.degrade(),
     );
 
     info!("Init Wireless...");
@@ -0,0 +1,33 @@
+//! # Menu
+//! An on-device menu on the LCD to set up alarms, the volume and timers without a network.
+//!
+//! Pressing the up or down button opens the menu. While it is open, a short press of
+//! the alarm button selects and a long press goes back, unless an alarm is ringing.
+//! The menu closes by itself when no button is pressed for a while.
+
+mod screen;
+mod task;
+
+use embassy_executor::Spawner;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use esp_hal::gpio::AnyPin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub(crate) enum MenuInput {
+    Up,
+    Down,
+    Select,
+    Back,
+}
+
+/// The inbox for button presses meant for the menu.
+pub(crate) static MENU_INPUTS: Channel<CriticalSectionRawMutex, MenuInput, 4> = Channel::new();
+
+/// Whether the menu is shown. The alarm button controls the menu while set.
+pub(crate) static MENU_OPEN: portable_atomic::AtomicBool = portable_atomic::AtomicBool::new(false);
+
+pub(super) fn init(spawner: Spawner, up_pin: AnyPin<'static>, down_pin: AnyPin<'static>) {
+    spawner.spawn(task::menu_task().unwrap());
+    spawner.spawn(task::nav_button_task(up_pin, MenuInput::Up).unwrap());
+    spawner.spawn(task::nav_button_task(down_pin, MenuInput::Down).unwrap());
+}
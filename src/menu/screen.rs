@@ -0,0 +1,291 @@
+//! # Menu Screens
+//! The screens of the on-device menu and how they react to the buttons.
+//!
+//! Screens only hold a working copy of what is being edited.
+//! Changes are applied by the menu task through the returned [`Effect`].
+
+use core::fmt::Write as _;
+
+use chrono::{NaiveTime, Timelike as _};
+
+use super::MenuInput;
+use crate::{
+    lcd::LcdDisplayString,
+    rtc_ds3231::{
+        recurrence::Recurrence,
+        schedule::{AlarmEntry, AlarmLabel},
+    },
+};
+
+/// How much the volume changes per press.
+const VOLUME_STEP: u8 = 5;
+
+/// The longest timer that can be started from the menu.
+const MAX_TIMER_MINUTES: u16 = 180;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum MainItem {
+    Alarms,
+    Volume,
+    Timer,
+}
+
+impl MainItem {
+    const ALL: [Self; 3] = [Self::Alarms, Self::Volume, Self::Timer];
+
+    #[inline]
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Alarms => "Alarms",
+            Self::Volume => "Volume",
+            Self::Timer => "Start timer",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The part of an alarm being edited.
+pub(super) enum Field {
+    Hour,
+    Minute,
+    Enabled,
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum Screen {
+    Main(MainItem),
+    /// Browses the alarms, with one extra item at the end to add an alarm.
+    Alarms {
+        idx: usize,
+    },
+    EditAlarm {
+        entry: AlarmEntry,
+        is_new: bool,
+        field: Field,
+    },
+    Volume(u8),
+    Timer {
+        minutes: u16,
+    },
+}
+
+/// What the menu task must do after handling an input.
+pub(super) enum Effect {
+    None,
+    /// Leaves the menu.
+    Close,
+    /// Opens the alarm list. The menu task fills in the alarms.
+    ShowAlarms,
+    /// Saves the edited alarm.
+    SaveAlarm {
+        entry: AlarmEntry,
+        is_new: bool,
+    },
+    SetVolume(u8),
+    StartTimer {
+        minutes: u16,
+    },
+}
+
+impl Screen {
+    #[inline]
+    pub const fn new() -> Self {
+        Self::Main(MainItem::Alarms)
+    }
+
+    /// Handles a button press.
+    ///
+    /// `alarms` are the entries of the alarm table and `volume` the set volume.
+    pub fn handle(&mut self, input: MenuInput, alarms: &[AlarmEntry], volume: u8) -> Effect {
+        match self {
+            Self::Main(item) => match input {
+                MenuInput::Up => *item = cycle(&MainItem::ALL, *item, false),
+                MenuInput::Down => *item = cycle(&MainItem::ALL, *item, true),
+                MenuInput::Back => return Effect::Close,
+                MenuInput::Select => match item {
+                    MainItem::Alarms => return Effect::ShowAlarms,
+                    MainItem::Volume => *self = Self::Volume(volume),
+                    MainItem::Timer => *self = Self::Timer { minutes: 5 },
+                },
+            },
+            Self::Alarms { idx } => {
+                // The extra item adds an alarm
+                let count = alarms.len().saturating_add(1);
+                match input {
+                    MenuInput::Up => *idx = idx.checked_sub(1).unwrap_or(count.saturating_sub(1)),
+                    MenuInput::Down => *idx = idx.saturating_add(1).rem_euclid(count),
+                    MenuInput::Back => *self = Self::Main(MainItem::Alarms),
+                    MenuInput::Select => {
+                        let (entry, is_new) = match alarms.get(*idx) {
+                            Some(entry) => (entry.clone(), false),
+                            None => (new_alarm(), true),
+                        };
+                        *self = Self::EditAlarm {
+                            entry,
+                            is_new,
+                            field: Field::Hour,
+                        };
+                    }
+                }
+            }
+            Self::EditAlarm {
+                entry,
+                is_new,
+                field,
+            } => match input {
+                MenuInput::Up => adjust_alarm(entry, *field, true),
+                MenuInput::Down => adjust_alarm(entry, *field, false),
+                MenuInput::Back => return Effect::ShowAlarms,
+                MenuInput::Select => match field {
+                    Field::Hour => *field = Field::Minute,
+                    Field::Minute => *field = Field::Enabled,
+                    Field::Enabled => {
+                        return Effect::SaveAlarm {
+                            entry: entry.clone(),
+                            is_new: *is_new,
+                        };
+                    }
+                },
+            },
+            Self::Volume(vol) => match input {
+                MenuInput::Up => {
+                    *vol = vol.saturating_add(VOLUME_STEP).min(100);
+                    return Effect::SetVolume(*vol);
+                }
+                MenuInput::Down => {
+                    *vol = vol.saturating_sub(VOLUME_STEP);
+                    return Effect::SetVolume(*vol);
+                }
+                MenuInput::Select | MenuInput::Back => *self = Self::Main(MainItem::Volume),
+            },
+            Self::Timer { minutes } => match input {
+                MenuInput::Up => *minutes = minutes.saturating_add(1).min(MAX_TIMER_MINUTES),
+                MenuInput::Down => *minutes = minutes.saturating_sub(1).max(1),
+                MenuInput::Back => *self = Self::Main(MainItem::Timer),
+                MenuInput::Select => {
+                    let minutes = *minutes;
+                    *self = Self::Main(MainItem::Timer);
+                    return Effect::StartTimer { minutes };
+                }
+            },
+        }
+
+        Effect::None
+    }
+
+    /// Returns the two lines to show on the LCD.
+    pub fn render(&self, alarms: &[AlarmEntry]) -> (LcdDisplayString, LcdDisplayString) {
+        let mut top = LcdDisplayString::new();
+        let mut bottom = LcdDisplayString::new();
+
+        // Lines are kept within 16 characters, so they are never truncated
+        let _ = match self {
+            Self::Main(item) => {
+                let pos = MainItem::ALL.iter().position(|i| i == item).unwrap_or(0);
+                write!(
+                    top,
+                    "Menu {}/{}",
+                    pos.saturating_add(1),
+                    MainItem::ALL.len()
+                )
+                .and_then(|()| write!(bottom, "> {}", item.name()))
+            }
+            Self::Alarms { idx } => match alarms.get(*idx) {
+                Some(entry) => write!(top, "Alarm {}/{}", idx.saturating_add(1), alarms.len())
+                    .and_then(|()| {
+                        write!(
+                            bottom,
+                            "{:02}:{:02} {:<3} {}",
+                            entry.time.hour(),
+                            entry.time.minute(),
+                            on_off(entry.enabled),
+                            entry.label.get(..6).unwrap_or(&entry.label)
+                        )
+                    }),
+                None => write!(top, "Alarms").and_then(|()| write!(bottom, "+ New alarm")),
+            },
+            Self::EditAlarm { entry, field, .. } => {
+                let (hour, min, enabled) = (
+                    entry.time.hour(),
+                    entry.time.minute(),
+                    on_off(entry.enabled),
+                );
+                let title = match field {
+                    Field::Hour => "Set hour",
+                    Field::Minute => "Set minute",
+                    Field::Enabled => "Turn on/off",
+                };
+
+                write!(top, "{title}").and_then(|()| match field {
+                    Field::Hour => write!(bottom, "[{hour:02}]:{min:02}  {enabled}"),
+                    Field::Minute => write!(bottom, "{hour:02}:[{min:02}]  {enabled}"),
+                    Field::Enabled => write!(bottom, "{hour:02}:{min:02} [{enabled}]"),
+                })
+            }
+            Self::Volume(vol) => write!(top, "Volume").and_then(|()| write!(bottom, "{vol}%")),
+            Self::Timer { minutes } => {
+                write!(top, "Start timer").and_then(|()| write!(bottom, "{minutes} min"))
+            }
+        };
+
+        (top, bottom)
+    }
+}
+
+#[inline]
+const fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+/// Returns the item after (or before) `current`, wrapping around.
+fn cycle<T: Copy + PartialEq>(items: &[T], current: T, forward: bool) -> T {
+    let pos = items.iter().position(|i| *i == current).unwrap_or(0);
+    let next = if forward {
+        pos.saturating_add(1).rem_euclid(items.len())
+    } else {
+        pos.checked_sub(1).unwrap_or(items.len().saturating_sub(1))
+    };
+
+    items.get(next).copied().unwrap_or(current)
+}
+
+/// A daily alarm at 07:00 for the "New alarm" item.
+fn new_alarm() -> AlarmEntry {
+    let mut label = AlarmLabel::new();
+    // Cannot fail since the label fits
+    let _ = label.push_str("Alarm");
+
+    AlarmEntry {
+        // ID is assigned by the table
+        id: 0,
+        label,
+        enabled: true,
+        time: NaiveTime::from_hms_opt(7, 0, 0).unwrap_or_default(),
+        recurrence: Recurrence::Daily,
+    }
+}
+
+/// Raises or lowers the `field` of an alarm by one, wrapping around.
+fn adjust_alarm(entry: &mut AlarmEntry, field: Field, up: bool) {
+    let step = |value: u32, max: u32| {
+        if up {
+            value.saturating_add(1).rem_euclid(max)
+        } else {
+            value.checked_sub(1).unwrap_or(max.saturating_sub(1))
+        }
+    };
+
+    let (hour, min) = (entry.time.hour(), entry.time.minute());
+    let time = match field {
+        Field::Hour => NaiveTime::from_hms_opt(step(hour, 24), min, 0),
+        Field::Minute => NaiveTime::from_hms_opt(hour, step(min, 60), 0),
+        Field::Enabled => {
+            entry.enabled = !entry.enabled;
+            return;
+        }
+    };
+
+    if let Some(time) = time {
+        entry.time = time;
+    }
+}
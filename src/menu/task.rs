@@ -0,0 +1,142 @@
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use esp_hal::gpio::{AnyPin, Input, InputConfig, Pull};
+
+use super::{
+    MENU_INPUTS, MENU_OPEN, MenuInput,
+    screen::{Effect, Screen},
+};
+use crate::{
+    buzzer::{BUZZER_COMMANDS, BUZZER_VOLUME, BuzzerAction},
+    lcd::LCD_MENU,
+    rtc_ds3231::{
+        ALARM_TABLE, RTC_COMMANDS, RtcCommand,
+        schedule::{AlarmEntry, MAX_ALARMS},
+    },
+    timer::{TIMERS, TIMERS_CHANGED, table::TimerLabel},
+};
+
+/// The menu closes after this long without a button press.
+const MENU_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a button must be held before it repeats.
+const REPEAT_DELAY: Duration = Duration::from_millis(500);
+
+/// How often a held button repeats.
+const REPEAT_RATE: Duration = Duration::from_millis(150);
+
+type Alarms = heapless::Vec<AlarmEntry, MAX_ALARMS>;
+
+#[embassy_executor::task]
+/// Opens the menu on the first button press and runs it until it is closed.
+pub(super) async fn menu_task() -> ! {
+    loop {
+        // The press that opens the menu does nothing else
+        MENU_INPUTS.receive().await;
+
+        info!("[menu] Opened");
+        MENU_OPEN.store(true, core::sync::atomic::Ordering::Release);
+        run_menu().await;
+        MENU_OPEN.store(false, core::sync::atomic::Ordering::Release);
+
+        LCD_MENU.signal(None);
+        info!("[menu] Closed");
+    }
+}
+
+/// Shows the menu and handles button presses until it is closed or times out.
+async fn run_menu() {
+    let mut screen = Screen::new();
+    let mut alarms = load_alarms().await;
+
+    loop {
+        let (top, bottom) = screen.render(&alarms);
+        LCD_MENU.signal(Some((top, bottom)));
+
+        let Ok(input) = with_timeout(MENU_TIMEOUT, MENU_INPUTS.receive()).await else {
+            return;
+        };
+
+        let volume = BUZZER_VOLUME.load(core::sync::atomic::Ordering::Acquire);
+        match screen.handle(input, &alarms, volume) {
+            Effect::None => {}
+            Effect::Close => return,
+            Effect::ShowAlarms => {
+                alarms = load_alarms().await;
+                screen = Screen::Alarms { idx: 0 };
+            }
+            Effect::SaveAlarm { entry, is_new } => {
+                save_alarm(entry, is_new).await;
+                alarms = load_alarms().await;
+                screen = Screen::Alarms { idx: 0 };
+            }
+            Effect::SetVolume(vol) => {
+                BUZZER_COMMANDS
+                    .send(BuzzerAction::SetVolume(vol).into())
+                    .await;
+            }
+            Effect::StartTimer { minutes } => start_timer(minutes).await,
+        }
+    }
+}
+
+#[inline]
+async fn load_alarms() -> Alarms {
+    ALARM_TABLE.read().await.entries().iter().cloned().collect()
+}
+
+async fn save_alarm(entry: AlarmEntry, is_new: bool) {
+    let result = {
+        let mut table = ALARM_TABLE.write().await;
+        if is_new {
+            table
+                .insert(entry.label, entry.enabled, entry.time, entry.recurrence)
+                .map(|_| ())
+        } else {
+            table.update(entry)
+        }
+    };
+
+    match result {
+        Ok(()) => RTC_COMMANDS.send(RtcCommand::Reschedule.into()).await,
+        Err(e) => warn!("[menu] Failed to save alarm: {}", e),
+    }
+}
+
+async fn start_timer(minutes: u16) {
+    let mut label = TimerLabel::new();
+    // Cannot fail since the label fits
+    let _ = label.push_str("Menu");
+
+    let duration = Duration::from_secs(u64::from(minutes).saturating_mul(60));
+    match TIMERS.write().await.start(label, duration, Instant::now()) {
+        Ok(id) => info!("[menu] Started timer {}", id),
+        Err(e) => warn!("[menu] Failed to start timer: {}", e),
+    }
+    TIMERS_CHANGED.signal(());
+}
+
+#[embassy_executor::task(pool_size = 2)]
+/// Sends `input` to the menu when the button is pressed, repeating while held.
+pub(super) async fn nav_button_task(pin: AnyPin<'static>, input: MenuInput) -> ! {
+    let mut button = Input::new(pin, InputConfig::default().with_pull(Pull::Up));
+
+    loop {
+        button.wait_for_falling_edge().await;
+
+        // Debounce before checking if the button is still held
+        Timer::after_millis(30).await;
+        if button.is_high() {
+            continue;
+        }
+
+        MENU_INPUTS.send(input).await;
+
+        let mut delay = REPEAT_DELAY;
+        while let Either::Second(()) = select(button.wait_for_high(), Timer::after(delay)).await {
+            MENU_INPUTS.send(input).await;
+            delay = REPEAT_RATE;
+        }
+    }
+}
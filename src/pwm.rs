@@ -9,6 +9,15 @@ use esp_hal::{
 
 use crate::utils::mk_static;
 
+/// The frequency of Timer0 unless a tone is being played.
+pub(crate) const BASE_FREQUENCY_HZ: u32 = 1000;
+
+/// The number of duty steps at 12-bit resolution. Full duty is `DUTY_RANGE`.
+pub(crate) const DUTY_RANGE: u32 = 1 << 12;
+
+/// The range of frequencies Timer0 can be set to at 12-bit duty resolution.
+pub(crate) const TONE_RANGE_HZ: core::ops::RangeInclusive<u32> = 20..=19_000;
+
 pub(crate) struct ChannelBuilder {
     lstimer: &'static Timer<'static, LowSpeed>,
     ledc: &'static Ledc<'static>,
@@ -72,10 +81,31 @@ pub(crate) fn init(ledc: peripherals::LEDC<'static>) -> Channels {
         lstimer0.configure(timer::config::Config {
             duty: esp_hal::ledc::timer::config::Duty::Duty12Bit,
             clock_source: esp_hal::ledc::timer::LSClockSource::APBClk,
-            frequency: esp_hal::time::Rate::from_hz(1000),
+            frequency: esp_hal::time::Rate::from_hz(BASE_FREQUENCY_HZ),
         }),
         "Failed to configure PWM Timer"
     );
 
     init_channels(lstimer0, ledc)
 }
+
+/// Changes the frequency of Timer0 while keeping its channels running.
+///
+/// [`TimerIFace::configure`] cannot be used since the channels hold a shared
+/// reference to the timer, so the clock divider is written to directly.
+/// Assumes Timer0 is clocked by the 80 MHz APB clock at 12-bit duty resolution.
+///
+/// `hz` is clamped to [`TONE_RANGE_HZ`].
+pub(crate) fn set_timer0_frequency(hz: u32) {
+    let hz = hz.clamp(*TONE_RANGE_HZ.start(), *TONE_RANGE_HZ.end());
+
+    // The divider is fixed point with 8 fractional bits.
+    // 80 MHz * 2^8 / 2^12 = 5 MHz
+    let divider = 5_000_000_u32.checked_div(hz).unwrap_or(u32::MAX);
+
+    peripherals::LEDC::regs().timer(0).conf().modify(|_, w| {
+        // SAFETY: The divider is within the 18 bits of `clk_div` since `hz` is clamped.
+        unsafe { w.clk_div().bits(divider) };
+        w.para_up().set_bit()
+    });
+}
@@ -1,4 +1,32 @@
-use super::{RtcDS3231, error::RtcError};
+use chrono::{NaiveDateTime, Utc};
+use ds3231::Alarm1Config;
+use rusty_clock_core::rtc::alarm::last_occurrence;
+
+use super::{RtcDS3231, error::RtcError, rtc_time::RtcDateTime};
+
+#[derive(Debug, Clone, Copy)]
+#[expect(dead_code, reason = "Read through Debug by /alarm/missed")]
+/// An alarm that went off while the device was powered off or rebooting.
+pub(crate) struct MissedAlarm {
+    /// When the alarm went off.
+    pub at: RtcDateTime<Utc>,
+    /// Whether the buzzer was rung on boot.
+    pub rang: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, defmt::Format)]
+/// Reports which of the DS3231 alarms fired.
+pub(crate) struct FiredAlarms {
+    pub alarm1: bool,
+    pub alarm2: bool,
+}
+
+impl FiredAlarms {
+    #[inline]
+    pub const fn any(self) -> bool {
+        self.alarm1 || self.alarm2
+    }
+}
 
 /// Clears and Sets Alarm1 Flag.
 pub(super) async fn reset_alarm1_flags(rtc: &mut RtcDS3231) -> Result<(), RtcError> {
@@ -18,3 +46,56 @@ pub(super) async fn reset_alarm1_flags(rtc: &mut RtcDS3231) -> Result<(), RtcErr
     defmt::debug!("[rtc:init] Alarm 1 interrupt enabled");
     Ok(())
 }
+
+/// Disables the Alarm1 interrupt, e.g. when no alarms are scheduled.
+pub(super) async fn disable_alarm1_interrupt(rtc: &mut RtcDS3231) -> Result<(), RtcError> {
+    let mut control = rtc.control().await?;
+    control.set_alarm1_interrupt_enable(false);
+    rtc.set_control(control).await?;
+
+    #[cfg(debug_assertions)]
+    defmt::debug!("[rtc] Alarm 1 interrupt disabled");
+    Ok(())
+}
+
+/// Clears and Sets Alarm2 Flag.
+pub(super) async fn reset_alarm2_flags(rtc: &mut RtcDS3231) -> Result<(), RtcError> {
+    clear_alarm2_flag(rtc).await?;
+
+    // Enable Alarm 2 interrupt
+    let mut control = rtc.control().await?;
+    control.set_alarm2_interrupt_enable(true);
+    rtc.set_control(control).await?;
+
+    #[cfg(debug_assertions)]
+    defmt::debug!("[rtc] Alarm 2 interrupt enabled");
+    Ok(())
+}
+
+/// Clears Alarm2 Flag without touching the interrupt enable bit.
+pub(super) async fn clear_alarm2_flag(rtc: &mut RtcDS3231) -> Result<(), RtcError> {
+    let mut status = rtc.status().await?;
+    status.set_alarm2_flag(false);
+    rtc.set_status(status).await?;
+
+    #[cfg(debug_assertions)]
+    defmt::debug!("[rtc] Alarm 2 flag cleared");
+    Ok(())
+}
+
+/// Checks whether Alarm1 went off while the device was powered off.
+///
+/// Must be called before the Alarm1 flag is reset.
+/// Returns the datetime in UTC at which the alarm went off.
+pub(super) async fn check_missed_alarm1(
+    rtc: &mut RtcDS3231,
+    config: &Alarm1Config,
+) -> Result<Option<NaiveDateTime>, RtcError> {
+    let status = rtc.status().await?;
+    if !status.alarm1_flag() {
+        return Ok(None);
+    }
+
+    let now = rtc.datetime().await?;
+    Ok(last_occurrence(config, now))
+}
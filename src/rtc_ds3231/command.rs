@@ -1,6 +1,7 @@
 use super::rtc_time::RtcDateTime;
 use crate::priority_command::Discriminant;
 use chrono::Utc;
+use embassy_time::Instant;
 
 #[repr(u8)]
 /// RTC Commands.
@@ -9,10 +10,37 @@ pub(crate) enum RtcCommand {
     Tick,
     /// Sets datetime for RTC.
     SetDateTime(RtcDateTime<Utc>),
+    /// Sets datetime for RTC once `at` is reached, e.g. exactly on a second boundary.
+    ///
+    /// Reports how late the write was through [`DATETIME_SET_SIGNAL`](super::DATETIME_SET_SIGNAL).
+    SetDateTimeAt {
+        datetime: RtcDateTime<Utc>,
+        at: Instant,
+    },
+    /// Finds when the RTC's current second started.
+    ///
+    /// Reports the result through [`SECOND_EDGE_SIGNAL`](super::SECOND_EDGE_SIGNAL).
+    FindSecondEdge,
     /// Sets the RTC module alarm.
+    ///
+    /// This overrides the [`ALARM_TABLE`](super::ALARM_TABLE) schedule
+    /// until [`RtcCommand::Reschedule`] is sent.
     SetAlarm(ds3231::Alarm1Config),
     /// Clears the alarm flags for RTC.
     ClearFlags,
+    /// Programs Alarm1 with the next due entry of [`ALARM_TABLE`](super::ALARM_TABLE).
+    Reschedule,
+    /// Handles an INT/SQW interrupt.
+    ///
+    /// Checks which alarms fired, clears their flags, re-arms Alarm1 and reports
+    /// the result through [`ALARM_FIRED_SIGNAL`](super::ALARM_FIRED_SIGNAL).
+    AlarmFired,
+    /// Sets the RTC module's second alarm.
+    SetAlarm2(ds3231::Alarm2Config),
+    /// Clears the Alarm2 flag for RTC.
+    ClearAlarm2Flags,
+    /// Writes the aging offset to trim the oscillator.
+    SetAgingOffset(i8),
 }
 
 // SAFETY: `RtcCommand` is `#[repr(u8)]`.
@@ -7,6 +7,8 @@ pub(crate) enum RtcError {
     I2cError(#[from] esp_hal::i2c::master::Error),
     #[error("Error configuring RTC: {0:?}")]
     DS3231Error(DS3231Error<I2cDeviceError<esp_hal::i2c::master::Error>>),
+    #[error("I2c Device Error: {0:?}")]
+    I2cDevice(I2cDeviceError<esp_hal::i2c::master::Error>),
 }
 
 impl From<DS3231Error<I2cDeviceError<esp_hal::i2c::master::Error>>> for RtcError {
@@ -14,3 +16,9 @@ impl From<DS3231Error<I2cDeviceError<esp_hal::i2c::master::Error>>> for RtcError
         Self::DS3231Error(value)
     }
 }
+
+impl From<I2cDeviceError<esp_hal::i2c::master::Error>> for RtcError {
+    fn from(value: I2cDeviceError<esp_hal::i2c::master::Error>) -> Self {
+        Self::I2cDevice(value)
+    }
+}
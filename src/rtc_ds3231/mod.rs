@@ -8,16 +8,24 @@
 pub mod alarm;
 pub(crate) mod command;
 pub mod error;
+mod registers;
 pub mod rtc_time;
+pub mod schedule;
 mod task;
+pub(crate) mod tz;
 use crate::priority_command::Priority;
-use alarm::reset_alarm1_flags;
+use alarm::{FiredAlarms, MissedAlarm, check_missed_alarm1, clear_alarm2_flag, reset_alarm1_flags};
 pub(crate) use command::RtcCommand;
+use drift::DriftLog;
 use rtc_time::RtcDateTime;
+pub use rusty_clock_core::rtc::recurrence;
+pub(crate) use rusty_clock_core::rtc::{drift, local_alarm};
+use schedule::AlarmTable;
 
-use chrono::Utc;
+use chrono::{Timelike as _, Utc};
+use core::sync::atomic::Ordering;
 use ds3231::{
-    Alarm1Config, Config, DS3231, InterruptControl, Oscillator, SquareWaveFrequency,
+    Alarm1Config, Alarm2Config, Config, DS3231, InterruptControl, Oscillator, SquareWaveFrequency,
     TimeRepresentation,
 };
 use embassy_executor::Spawner;
@@ -25,10 +33,16 @@ use embassy_sync::{
     blocking_mutex::raw::CriticalSectionRawMutex,
     priority_channel::{Min, PriorityChannel},
     rwlock::RwLock,
+    signal::Signal,
     watch::Watch,
 };
+use embassy_time::{Duration, Instant};
 
-use crate::i2c::I2cBus;
+use crate::{
+    buzzer::{RING_COMMANDS, RingAction, ring::RingSource},
+    i2c::I2cBus,
+    lcd::{LCD_COMMANDS, LcdAction},
+};
 
 /// The alarm time set through env.
 /// NOTE: Time stored in RTC is in UTC, adjust to your timezone.
@@ -67,10 +81,64 @@ const ENV_TIME: Alarm1Config = {
 /// Contains the time from RTC module.
 pub(crate) static TIME_WATCH: Watch<CriticalSectionRawMutex, RtcDateTime<Utc>, 3> = Watch::new();
 
+/// Whether the RTC time can be trusted.
+///
+/// Cleared if the DS3231 reports that its oscillator stopped, and set again
+/// once the time is set. Alarms do not ring while it is cleared.
+pub(crate) static TIME_VALID: portable_atomic::AtomicBool = portable_atomic::AtomicBool::new(true);
+
+/// Signals that the RTC lost its time and should be synced with NTP.
+pub(crate) static TIME_LOST_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
 /// Globally accessible [`Alarm1Config`].
 pub(crate) static ALARM_CONFIG_RWLOCK: RwLock<CriticalSectionRawMutex, Alarm1Config> =
     RwLock::new(ENV_TIME);
 
+/// Globally accessible [`Alarm2Config`].
+///
+/// `None` if Alarm2 has not been set since boot.
+pub(crate) static ALARM2_CONFIG_RWLOCK: RwLock<CriticalSectionRawMutex, Option<Alarm2Config>> =
+    RwLock::new(None);
+
+/// Signals which alarms fired after [`RtcCommand::AlarmFired`] is handled.
+pub(crate) static ALARM_FIRED_SIGNAL: Signal<CriticalSectionRawMutex, FiredAlarms> = Signal::new();
+
+/// Signals how late the write was after [`RtcCommand::SetDateTimeAt`] is handled.
+pub(crate) static DATETIME_SET_SIGNAL: Signal<CriticalSectionRawMutex, Duration> = Signal::new();
+
+/// Signals the RTC datetime and when it started after [`RtcCommand::FindSecondEdge`] is handled.
+///
+/// `None` if the RTC could not be read.
+pub(crate) static SECOND_EDGE_SIGNAL: Signal<
+    CriticalSectionRawMutex,
+    Option<(RtcDateTime<Utc>, Instant)>,
+> = Signal::new();
+
+/// Drift of the RTC measured against NTP, and its aging offset.
+pub(crate) static DRIFT_LOG: RwLock<CriticalSectionRawMutex, DriftLog> =
+    RwLock::new(DriftLog::new());
+
+/// The alarm that went off while the device was powered off, if any.
+pub(crate) static MISSED_ALARM: RwLock<CriticalSectionRawMutex, Option<MissedAlarm>> =
+    RwLock::new(None);
+
+/// Missed alarms that went off within this many minutes before boot still ring.
+const MISSED_ALARM_GRACE_MINS: i64 = {
+    let mins = option_env!("MISSED_ALARM_GRACE_MINS").unwrap_or("15");
+    i64::from_str_radix(mins, 10)
+        .ok()
+        .expect("Failed to parse .env: MISSED_ALARM_GRACE_MINS")
+};
+
+// TEST: Grace window cannot be negative
+static_assertions::const_assert!(MISSED_ALARM_GRACE_MINS >= 0);
+
+/// Globally accessible [`AlarmTable`].
+///
+/// Send [`RtcCommand::Reschedule`] after modifying the table to re-arm Alarm1.
+pub(crate) static ALARM_TABLE: RwLock<CriticalSectionRawMutex, AlarmTable> =
+    RwLock::new(AlarmTable::new());
+
 /// The inbox for all RTC Commands.
 pub(crate) static RTC_COMMANDS: PriorityChannel<
     CriticalSectionRawMutex,
@@ -90,6 +158,22 @@ const RTC_I2C_ADDR: u8 = {
 
 /// Initialize the DS3231 Instance and spawn tasks.
 pub(crate) async fn init(spawner: Spawner, i2c: I2cBus) {
+    // Must be set before any local time is used
+    tz::init();
+
+    // Used to access registers not exposed by `ds3231`
+    let mut raw_i2c = i2c.clone();
+
+    // Checked first, in case configuring the RTC touches the status register
+    match registers::read_oscillator_stop_flag(&mut raw_i2c).await {
+        Ok(true) => mark_time_lost(),
+        Ok(false) => {}
+        Err(err) => defmt::error!(
+            "[rtc] Failed to read oscillator stop flag: {}",
+            defmt::Debug2Format(&err)
+        ),
+    }
+
     let config = Config {
         time_representation: TimeRepresentation::TwentyFourHour,
         square_wave_frequency: SquareWaveFrequency::Hz1,
@@ -102,6 +186,24 @@ pub(crate) async fn init(spawner: Spawner, i2c: I2cBus) {
         .await
         .expect("[rtc] Failed to configure");
 
+    // Production uses the previously set alarm, so read it back.
+    // Debug builds overwrite it with `ENV_TIME` below.
+    load_alarm1_config(&mut raw_i2c).await;
+
+    match registers::read_aging_offset(&mut raw_i2c).await {
+        Ok(aging) => {
+            defmt::info!("[rtc] Aging offset: {=i8}", aging);
+            DRIFT_LOG.write().await.set_aging(aging);
+        }
+        Err(err) => defmt::error!(
+            "[rtc] Failed to read aging offset: {}",
+            defmt::Debug2Format(&err)
+        ),
+    }
+
+    // Must be checked before the alarm is overwritten and its flag is reset
+    handle_missed_alarm(&mut rtc).await;
+
     #[cfg(debug_assertions)]
     {
         // Only set alarm in debug builds. Uses previously set alarm in production.
@@ -116,6 +218,96 @@ pub(crate) async fn init(spawner: Spawner, i2c: I2cBus) {
         .await
         .expect("[rtc] Failed to reset flags");
 
-    spawner.spawn(task::runner(rtc).unwrap());
+    // Alarm2 interrupt is only enabled once Alarm2 is set
+    clear_alarm2_flag(&mut rtc)
+        .await
+        .expect("[rtc] Failed to clear Alarm2 flag");
+
+    spawner.spawn(task::runner(rtc, raw_i2c).unwrap());
     spawner.spawn(task::heartbeat_task().unwrap());
 }
+
+/// Reads the alarm stored in the RTC and seeds [`ALARM_CONFIG_RWLOCK`] with it.
+///
+/// Keeps [`ENV_TIME`] if the registers cannot be read or decoded.
+async fn load_alarm1_config(i2c: &mut I2cBus) {
+    let regs = match registers::read_alarm1_registers(i2c).await {
+        Ok(regs) => regs,
+        Err(err) => {
+            defmt::error!(
+                "[rtc] Failed to read Alarm1 registers: {}",
+                defmt::Debug2Format(&err)
+            );
+            return;
+        }
+    };
+
+    let Some(config) = registers::decode_alarm1(regs) else {
+        defmt::warn!("[rtc] Invalid Alarm1 registers: {:#04x}", regs);
+        return;
+    };
+
+    defmt::info!("[rtc] Alarm1 Config: {}", config);
+    *ALARM_CONFIG_RWLOCK.write().await = config;
+}
+
+/// Marks the RTC time as not valid and asks for an NTP sync.
+///
+/// The LCD warns about it until the time is set again.
+fn mark_time_lost() {
+    if !TIME_VALID.swap(false, Ordering::AcqRel) {
+        return;
+    }
+
+    defmt::warn!("[rtc] Oscillator stopped. Time is not valid until set.");
+    TIME_LOST_SIGNAL.signal(());
+}
+
+/// Records an alarm that went off while the device was powered off.
+///
+/// Rings the buzzer if it went off within [`MISSED_ALARM_GRACE_MINS`],
+/// otherwise displays it on the LCD.
+async fn handle_missed_alarm(rtc: &mut RtcDS3231) {
+    if !TIME_VALID.load(Ordering::Acquire) {
+        defmt::warn!("[rtc] Time is not valid. Skipping missed alarm check.");
+        return;
+    }
+
+    let config = ALARM_CONFIG_RWLOCK.read().await;
+    let missed = match check_missed_alarm1(rtc, &config).await {
+        Ok(Some(at)) => at,
+        Ok(None) => return,
+        Err(err) => {
+            defmt::error!(
+                "[rtc] Failed to check for missed alarm: {}",
+                defmt::Debug2Format(&err)
+            );
+            return;
+        }
+    };
+    drop(config);
+
+    let Ok(now) = rtc.datetime().await else {
+        return;
+    };
+
+    let at: RtcDateTime<Utc> = missed.and_utc().into();
+    let rang =
+        now.signed_duration_since(missed) <= chrono::TimeDelta::minutes(MISSED_ALARM_GRACE_MINS);
+
+    if rang {
+        defmt::info!("[rtc] Alarm went off while powered off. Ringing.");
+        RING_COMMANDS
+            .send(RingAction::Start(RingSource::Alarm))
+            .await;
+    } else {
+        let local = at.local();
+        defmt::info!("[rtc] Missed alarm at {=str}", local.to_iso8601());
+
+        // Fits the 16 columns of the bottom line
+        let msg = heapless::format!("Missed {:02}:{:02}", local.hour(), local.minute()).unwrap();
+        LCD_COMMANDS.signal(LcdAction::Status(msg));
+    }
+
+    *MISSED_ALARM.write().await = Some(MissedAlarm { at, rang });
+}
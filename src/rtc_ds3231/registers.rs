@@ -0,0 +1,89 @@
+//! # DS3231 Registers
+//! This module provides raw access to DS3231 registers that are
+//! not exposed by the [`ds3231`] crate.
+
+use embedded_hal_async::i2c::I2c as _;
+
+use super::{RTC_I2C_ADDR, error::RtcError};
+use crate::i2c::I2cBus;
+
+pub(super) use rusty_clock_core::rtc::registers::decode_alarm1;
+
+/// Address of the Alarm1 seconds register.
+/// The minutes, hours and day/date registers follow it.
+const ALARM1_SECONDS_REG: u8 = 0x07;
+
+/// Address of the aging offset register, a two's complement value.
+const AGING_OFFSET_REG: u8 = 0x10;
+
+/// Address of the status register.
+const STATUS_REG: u8 = 0x0F;
+/// Oscillator stop flag of the status register. Set if the oscillator stopped,
+/// e.g. because the backup battery died, so the time is not valid.
+const OSF_BIT: u8 = 0b1000_0000;
+/// Alarm flags of the status register. Writing 1 leaves them unchanged.
+const ALARM_FLAG_BITS: u8 = 0b0000_0011;
+
+/// Address of the control register.
+const CONTROL_REG: u8 = 0x0E;
+/// Convert temperature bit of the control register.
+/// Starts a temperature conversion, which also applies the aging offset.
+const CONV_BIT: u8 = 0b0010_0000;
+
+/// Reads the Alarm1 registers.
+///
+/// Returns the seconds, minutes, hours and day/date registers in order.
+pub(super) async fn read_alarm1_registers(i2c: &mut I2cBus) -> Result<[u8; 4], RtcError> {
+    let mut regs = [0; 4];
+    i2c.write_read(RTC_I2C_ADDR, &[ALARM1_SECONDS_REG], &mut regs)
+        .await?;
+    Ok(regs)
+}
+
+/// Reads the aging offset register.
+pub(super) async fn read_aging_offset(i2c: &mut I2cBus) -> Result<i8, RtcError> {
+    let mut reg = [0];
+    i2c.write_read(RTC_I2C_ADDR, &[AGING_OFFSET_REG], &mut reg)
+        .await?;
+    let [reg] = reg;
+    Ok(reg.cast_signed())
+}
+
+/// Writes the aging offset register.
+///
+/// Also starts a temperature conversion, otherwise the new offset
+/// only takes effect with the next automatic one up to 64 seconds later.
+pub(super) async fn write_aging_offset(i2c: &mut I2cBus, aging: i8) -> Result<(), RtcError> {
+    i2c.write(RTC_I2C_ADDR, &[AGING_OFFSET_REG, aging.cast_unsigned()])
+        .await?;
+
+    let mut control = [0];
+    i2c.write_read(RTC_I2C_ADDR, &[CONTROL_REG], &mut control)
+        .await?;
+    let [control] = control;
+    i2c.write(RTC_I2C_ADDR, &[CONTROL_REG, control | CONV_BIT])
+        .await?;
+    Ok(())
+}
+
+/// Reads the oscillator stop flag.
+pub(super) async fn read_oscillator_stop_flag(i2c: &mut I2cBus) -> Result<bool, RtcError> {
+    let mut status = [0];
+    i2c.write_read(RTC_I2C_ADDR, &[STATUS_REG], &mut status)
+        .await?;
+    let [status] = status;
+    Ok(status & OSF_BIT != 0)
+}
+
+/// Clears the oscillator stop flag without touching the alarm flags.
+pub(super) async fn clear_oscillator_stop_flag(i2c: &mut I2cBus) -> Result<(), RtcError> {
+    let mut status = [0];
+    i2c.write_read(RTC_I2C_ADDR, &[STATUS_REG], &mut status)
+        .await?;
+    let [status] = status;
+
+    // Alarm flags that were set in the meantime are kept
+    let status = (status & !OSF_BIT) | ALARM_FLAG_BITS;
+    i2c.write(RTC_I2C_ADDR, &[STATUS_REG, status]).await?;
+    Ok(())
+}
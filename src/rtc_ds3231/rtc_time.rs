@@ -1,18 +1,28 @@
 //! # `RtcDateTime`
 //! This module provides all functionalities regarding [`RtcDateTime`].
 
-use chrono::{DateTime, Datelike, FixedOffset, TimeZone, Timelike, Utc};
-use core::{fmt::Debug, hint::assert_unchecked, ops::Deref};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDateTime, TimeZone, Timelike, Utc};
+use core::{
+    fmt::Debug,
+    hint::assert_unchecked,
+    ops::{Deref, RangeInclusive},
+};
 
-use crate::TZ_OFFSET;
+use super::tz;
 
-/// A [`chrono::FixedOffset`] generated from [`TZ_OFFSET`].
-///
-/// This is used to convert between local and UTC time.
-const FIXED_OFFSET: chrono::FixedOffset = {
-    chrono::FixedOffset::east_opt(i32::from(TZ_OFFSET).saturating_mul(3600))
-        .expect("Failed to convert `TZ_OFFSET` to `chrono::FixedOffset`.")
-};
+/// The years the DS3231 can store, since it only keeps the last two digits.
+const YEAR_RANGE: RangeInclusive<i32> = 2000..=2099;
+
+/// Formats of datetimes without an offset, which are read as local time.
+const LOCAL_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub(crate) enum ParseTimeError {
+    #[error("Not an ISO 8601 datetime or Unix timestamp")]
+    Invalid,
+    #[error("Year is not within 2000 to 2099")]
+    OutOfRange,
+}
 
 const MONTH_BY_INDEX: [&str; 12] = [
     "January",
@@ -95,10 +105,10 @@ impl<TZ: TimeZone> RtcDateTime<TZ> {
 
 impl RtcDateTime<Utc> {
     #[inline]
-    /// Converts itself to `Local` variant.
+    /// Converts itself to `Local` variant using the time zone in use.
     pub fn local(self) -> RtcDateTime<FixedOffset> {
-        let time = self.0.with_timezone(&FIXED_OFFSET);
-        RtcDateTime(time)
+        let offset = tz::current().offset(self.0.naive_utc());
+        RtcDateTime(self.0.with_timezone(&offset))
     }
 
     #[inline]
@@ -107,10 +117,28 @@ impl RtcDateTime<Utc> {
         chrono::Utc.timestamp_opt(ts, 0).unwrap().into()
     }
 
-    #[inline]
-    /// Returns seconds since Unix Epoch.
-    pub fn to_timestamp(&self) -> u64 {
-        self.0.timestamp().cast_unsigned()
+    /// Parses an RFC 3339 timestamp, seconds since Unix Epoch, or an
+    /// ISO 8601 datetime without an offset, which is read as local time.
+    pub fn parse(s: &str) -> Result<Self, ParseTimeError> {
+        let s = s.trim();
+
+        let datetime = if let Ok(ts) = s.parse::<i64>() {
+            DateTime::from_timestamp(ts, 0).ok_or(ParseTimeError::OutOfRange)?
+        } else if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+            dt.to_utc()
+        } else {
+            let naive = LOCAL_FORMATS
+                .into_iter()
+                .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+                .ok_or(ParseTimeError::Invalid)?;
+            RtcDateTime::from_local(naive).utc().0
+        };
+
+        if !YEAR_RANGE.contains(&datetime.year()) {
+            return Err(ParseTimeError::OutOfRange);
+        }
+
+        Ok(Self(datetime))
     }
 
     #[inline]
@@ -131,7 +159,15 @@ impl RtcDateTime<Utc> {
 
 impl RtcDateTime<FixedOffset> {
     #[inline]
-    #[expect(unused, reason = "Will use later")]
+    /// Interprets a [`NaiveDateTime`] as local time in the time zone in use.
+    ///
+    /// See [`tz::PosixTz::to_utc`] for times skipped or repeated by daylight saving time.
+    pub fn from_local(naive: NaiveDateTime) -> Self {
+        let utc = tz::current().to_utc(naive).and_utc();
+        RtcDateTime::from(utc).local()
+    }
+
+    #[inline]
     /// Converts itself to `Utc` variant.
     pub fn utc(self) -> RtcDateTime<Utc> {
         RtcDateTime(self.0.to_utc())
@@ -140,10 +176,12 @@ impl RtcDateTime<FixedOffset> {
     #[inline]
     /// Converts [`RtcDateTime`] to ISO8601-conformant string.
     pub fn to_iso8601(self) -> heapless::String<25> {
-        let sign = const { if TZ_OFFSET.is_positive() { "+" } else { "-" } };
+        let offset = self.0.offset().local_minus_utc();
+        let sign = if offset.is_negative() { "-" } else { "+" };
+        let mins = offset.unsigned_abs().div_euclid(60);
 
         heapless::format!(
-            "{}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:00",
+            "{}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
             self.0.year(),
             self.0.month(),
             self.0.day(),
@@ -151,7 +189,8 @@ impl RtcDateTime<FixedOffset> {
             self.0.minute(),
             self.0.second(),
             sign,
-            TZ_OFFSET
+            mins.div_euclid(60),
+            mins.rem_euclid(60),
         )
         .unwrap()
     }
@@ -0,0 +1,153 @@
+//! # Alarm Schedule
+//! A software alarm table layered on top of the DS3231's Alarm1.
+//!
+//! The DS3231 can only hold a single Alarm1 at a time, so the RTC runner
+//! always programs it with the next due [`AlarmEntry`] and re-arms it
+//! every time it fires.
+//!
+//! All times stored in the table are in local time.
+
+use chrono::{NaiveDateTime, NaiveTime, TimeDelta};
+
+use super::recurrence::Recurrence;
+
+/// The maximum number of alarms the [`AlarmTable`] can hold.
+pub(crate) const MAX_ALARMS: usize = 16;
+
+/// Simply an alias [`heapless::String`] used for alarm labels.
+pub(crate) type AlarmLabel = heapless::String<16>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub(crate) enum ScheduleError {
+    #[error("Alarm table is full")]
+    TableFull,
+    #[error("No alarm found with the given ID")]
+    NotFound,
+}
+
+#[derive(Debug, Clone)]
+/// A single alarm stored in the [`AlarmTable`].
+pub(crate) struct AlarmEntry {
+    pub id: u8,
+    pub label: AlarmLabel,
+    pub enabled: bool,
+    /// Time of day in local time.
+    pub time: NaiveTime,
+    pub recurrence: Recurrence,
+}
+
+impl AlarmEntry {
+    /// Returns the next local datetime, strictly after `now`, at which this entry fires.
+    ///
+    /// Returns `None` if the entry is disabled.
+    pub fn next_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        if !self.enabled {
+            return None;
+        }
+
+        self.recurrence.next_after(self.time, now)
+    }
+}
+
+#[derive(Debug)]
+/// Holds up to [`MAX_ALARMS`] alarms.
+pub(crate) struct AlarmTable {
+    entries: heapless::Vec<AlarmEntry, MAX_ALARMS>,
+}
+
+impl AlarmTable {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            entries: heapless::Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn entries(&self) -> &[AlarmEntry] {
+        &self.entries
+    }
+
+    #[inline]
+    pub fn get(&self, id: u8) -> Option<&AlarmEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    /// Inserts a new entry and returns the ID assigned to it.
+    ///
+    /// The entry is assigned the lowest unused ID.
+    pub fn insert(
+        &mut self,
+        label: AlarmLabel,
+        enabled: bool,
+        time: NaiveTime,
+        recurrence: Recurrence,
+    ) -> Result<u8, ScheduleError> {
+        let id = (0..=u8::MAX)
+            .find(|id| self.get(*id).is_none())
+            .ok_or(ScheduleError::TableFull)?;
+
+        self.entries
+            .push(AlarmEntry {
+                id,
+                label,
+                enabled,
+                time,
+                recurrence,
+            })
+            .map_err(|_| ScheduleError::TableFull)?;
+
+        Ok(id)
+    }
+
+    /// Replaces the entry with the same ID as `entry`.
+    pub fn update(&mut self, entry: AlarmEntry) -> Result<(), ScheduleError> {
+        let old = self
+            .entries
+            .iter_mut()
+            .find(|e| e.id == entry.id)
+            .ok_or(ScheduleError::NotFound)?;
+
+        *old = entry;
+        Ok(())
+    }
+
+    /// Removes the entry with the given ID.
+    pub fn remove(&mut self, id: u8) -> Result<AlarmEntry, ScheduleError> {
+        let idx = self
+            .entries
+            .iter()
+            .position(|e| e.id == id)
+            .ok_or(ScheduleError::NotFound)?;
+
+        Ok(self.entries.swap_remove(idx))
+    }
+
+    /// Returns the earliest local datetime, strictly after `now`, at which any entry fires.
+    pub fn next_due(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.entries.iter().filter_map(|e| e.next_after(now)).min()
+    }
+
+    /// Marks all entries due at `at` as fired and returns their IDs.
+    ///
+    /// [`Recurrence::Once`] entries are disabled.
+    pub fn fire(&mut self, at: NaiveDateTime) -> heapless::Vec<u8, MAX_ALARMS> {
+        let before = at.checked_sub_signed(TimeDelta::seconds(1)).unwrap_or(at);
+        let mut fired = heapless::Vec::new();
+
+        for entry in &mut self.entries {
+            if entry.next_after(before) != Some(at) {
+                continue;
+            }
+
+            if matches!(entry.recurrence, Recurrence::Once) {
+                entry.enabled = false;
+            }
+
+            // Cannot overflow since both hold at most `MAX_ALARMS`
+            let _ = fired.push(entry.id);
+        }
+
+        fired
+    }
+}
@@ -1,28 +1,77 @@
 //! # DS3231 RTC Tasks
 //! This module provides tasks related to our RTC module.
 
-use chrono::Utc;
-use ds3231::Alarm1Config;
+use chrono::{Datelike as _, NaiveDateTime, TimeDelta, Timelike as _, Utc};
+use ds3231::{Alarm1Config, Alarm2Config};
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, watch::Sender};
-use embassy_time::Timer;
+use embassy_time::{Duration, Instant, Timer};
 
 use super::{
-    ALARM_CONFIG_RWLOCK, RTC_COMMANDS, RtcCommand, RtcDS3231, TIME_WATCH, reset_alarm1_flags,
+    ALARM_CONFIG_RWLOCK, ALARM_FIRED_SIGNAL, ALARM_TABLE, ALARM2_CONFIG_RWLOCK,
+    DATETIME_SET_SIGNAL, DRIFT_LOG, RTC_COMMANDS, RtcCommand, RtcDS3231, SECOND_EDGE_SIGNAL,
+    TIME_VALID, TIME_WATCH,
+    alarm::{FiredAlarms, clear_alarm2_flag, disable_alarm1_interrupt, reset_alarm2_flags},
+    mark_time_lost, registers, reset_alarm1_flags,
     rtc_time::RtcDateTime,
 };
+use crate::i2c::I2cBus;
+use core::sync::atomic::Ordering;
+
+/// How often the RTC is read while looking for the start of a second.
+const EDGE_POLL: Duration = Duration::from_millis(2);
+
+/// Gives up looking for the start of a second after this long.
+const MAX_EDGE_WAIT: Duration = Duration::from_millis(1100);
 
 #[embassy_executor::task]
-pub(super) async fn runner(mut rtc: RtcDS3231) -> ! {
+pub(super) async fn runner(mut rtc: RtcDS3231, mut i2c: I2cBus) -> ! {
     let time_sender = TIME_WATCH.sender();
     let cmd_rx = RTC_COMMANDS.receiver();
     let mut count = 0;
+    // The local datetime of the schedule entry Alarm1 is currently armed with
+    let mut armed: Option<NaiveDateTime> = None;
 
     loop {
         match cmd_rx.receive().await.into_inner() {
-            RtcCommand::Tick => time_handle(&time_sender, &mut rtc, &mut count).await,
-            RtcCommand::SetDateTime(datetime) => set_datetime_handle(&mut rtc, datetime).await,
-            RtcCommand::SetAlarm(config) => alarm_handle(&mut rtc, config).await,
+            RtcCommand::Tick => {
+                time_handle(&time_sender, &mut rtc, &mut count).await;
+                oscillator_stop_handle(&mut i2c).await;
+            }
+            RtcCommand::SetDateTime(datetime) => {
+                // The error of a time set by hand is unknown
+                DRIFT_LOG.write().await.forget_set();
+                if set_datetime_handle(&mut rtc, datetime).await {
+                    time_restored_handle(&mut rtc, &mut i2c, &mut armed).await;
+                }
+            }
+            RtcCommand::SetDateTimeAt { datetime, at } => {
+                // Writing the seconds resets the DS3231's countdown, so the new second starts now
+                Timer::at(at).await;
+                let is_set = set_datetime_handle(&mut rtc, datetime).await;
+                DATETIME_SET_SIGNAL.signal(Instant::now().saturating_duration_since(at));
+
+                if is_set {
+                    time_restored_handle(&mut rtc, &mut i2c, &mut armed).await;
+                }
+            }
+            RtcCommand::FindSecondEdge => SECOND_EDGE_SIGNAL.signal(second_edge(&mut rtc).await),
+            RtcCommand::SetAlarm(config) => {
+                armed = None;
+                alarm_handle(&mut rtc, config).await;
+            }
             RtcCommand::ClearFlags => clear_flags_handle(&mut rtc).await,
+            RtcCommand::Reschedule => schedule_handle(&mut rtc, &mut armed).await,
+            RtcCommand::AlarmFired => interrupt_handle(&mut rtc, &mut armed).await,
+            RtcCommand::SetAlarm2(config) => alarm2_handle(&mut rtc, config).await,
+            RtcCommand::ClearAlarm2Flags => {
+                if let Err(err) = clear_alarm2_flag(&mut rtc).await {
+                    defmt::error!(
+                        "[rtc] Failed to clear Alarm2 flag: {}",
+                        defmt::Debug2Format(&err)
+                    );
+                }
+            }
+            RtcCommand::SetAgingOffset(aging) => aging_handle(&mut i2c, aging).await,
         }
     }
 }
@@ -59,14 +108,263 @@ async fn alarm_handle(rtc: &mut RtcDS3231, config: Alarm1Config) {
     *ALARM_CONFIG_RWLOCK.write().await = config;
 }
 
+/// Programs Alarm1 with the next due entry of [`ALARM_TABLE`].
+///
+/// Disables the Alarm1 interrupt if there are no enabled entries.
+async fn schedule_handle(rtc: &mut RtcDS3231, armed: &mut Option<NaiveDateTime>) {
+    let Some(now) = local_now(rtc).await else {
+        return;
+    };
+
+    let Some(next) = ALARM_TABLE.read().await.next_due(now) else {
+        defmt::info!("[rtc] No alarms scheduled");
+        *armed = None;
+
+        if let Err(err) = disable_alarm1_interrupt(rtc).await {
+            defmt::error!(
+                "[rtc] Failed to disable Alarm1: {}",
+                defmt::Debug2Format(&err)
+            );
+        }
+        return;
+    };
+
+    let utc = RtcDateTime::from_local(next).utc();
+    let config = Alarm1Config::AtTimeOnDate {
+        hours: utc.hour().truncate(),
+        minutes: utc.minute().truncate(),
+        seconds: utc.second().truncate(),
+        date: utc.day().truncate(),
+        is_pm: None,
+    };
+
+    *armed = Some(next);
+    alarm_handle(rtc, config).await;
+}
+
+/// Checks which alarms fired, clears their flags and signals
+/// the result to [`ALARM_FIRED_SIGNAL`].
+async fn interrupt_handle(rtc: &mut RtcDS3231, armed: &mut Option<NaiveDateTime>) {
+    let status = match rtc.status().await {
+        Ok(status) => status,
+        Err(err) => {
+            defmt::error!("[rtc] Failed to read status: {}", defmt::Debug2Format(&err));
+            // Let listeners know anyway rather than leaving them waiting
+            ALARM_FIRED_SIGNAL.signal(FiredAlarms::default());
+            return;
+        }
+    };
+
+    let mut fired = FiredAlarms::default();
+
+    // The alarms matched a time that is not valid
+    if !TIME_VALID.load(Ordering::Acquire) {
+        defmt::warn!("[rtc] Time is not valid. Ignoring alarms.");
+        clear_flags_handle(rtc).await;
+        if let Err(err) = clear_alarm2_flag(rtc).await {
+            defmt::error!(
+                "[rtc] Failed to clear Alarm2 flag: {}",
+                defmt::Debug2Format(&err)
+            );
+        }
+
+        ALARM_FIRED_SIGNAL.signal(fired);
+        return;
+    }
+
+    if status.alarm1_flag() {
+        fired.alarm1 = alarm1_fired_handle(rtc, armed).await;
+    }
+
+    if status.alarm2_flag() {
+        fired.alarm2 = true;
+        if let Err(err) = clear_alarm2_flag(rtc).await {
+            defmt::error!(
+                "[rtc] Failed to clear Alarm2 flag: {}",
+                defmt::Debug2Format(&err)
+            );
+        }
+    }
+
+    defmt::info!("[rtc] Alarms fired: {}", fired);
+    ALARM_FIRED_SIGNAL.signal(fired);
+}
+
+/// Marks due entries as fired and re-arms Alarm1.
+///
+/// If Alarm1 was not armed by the schedule, only the flags are cleared
+/// so the alarm stored in the RTC keeps working.
+///
+/// Returns `false` if Alarm1 fired before it was due.
+async fn alarm1_fired_handle(rtc: &mut RtcDS3231, armed: &mut Option<NaiveDateTime>) -> bool {
+    let Some(at) = *armed else {
+        clear_flags_handle(rtc).await;
+        return true;
+    };
+
+    let Some(now) = local_now(rtc).await else {
+        clear_flags_handle(rtc).await;
+        return true;
+    };
+
+    // Alarm1 only matches the date, so alarms over a month away
+    // fire early on the same date of an earlier month.
+    let earliest = at.checked_sub_signed(TimeDelta::minutes(1)).unwrap_or(at);
+    if now < earliest {
+        defmt::info!("[rtc] Alarm fired early. Re-arming.");
+        clear_flags_handle(rtc).await;
+        return false;
+    }
+
+    *armed = None;
+    for id in ALARM_TABLE.write().await.fire(at) {
+        defmt::info!("[rtc] Alarm {=u8} fired", id);
+    }
+
+    schedule_handle(rtc, armed).await;
+    true
+}
+
 #[inline]
-async fn set_datetime_handle(rtc: &mut RtcDS3231, datetime: RtcDateTime<Utc>) {
+async fn alarm2_handle(rtc: &mut RtcDS3231, config: Alarm2Config) {
+    defmt::info!("New Alarm2 Set: {}", config);
+
+    if let Err(err) = rtc.set_alarm2(&config).await {
+        defmt::error!("[rtc] Failed to set Alarm2: {}", defmt::Debug2Format(&err));
+        return;
+    }
+
+    if let Err(err) = reset_alarm2_flags(rtc).await {
+        defmt::error!("[rtc] Failed to reset flags: {}", defmt::Debug2Format(&err));
+        return;
+    }
+
+    *ALARM2_CONFIG_RWLOCK.write().await = Some(config);
+}
+
+/// Reads the current local datetime from the RTC.
+async fn local_now(rtc: &mut RtcDS3231) -> Option<NaiveDateTime> {
+    match rtc.datetime().await {
+        Ok(dt) => Some(RtcDateTime::from(dt.and_utc()).local().naive_local()),
+        Err(err) => {
+            defmt::error!(
+                "[rtc] Failed to read datetime: {}",
+                defmt::Debug2Format(&err)
+            );
+            None
+        }
+    }
+}
+
+/// Reads the RTC until its seconds change.
+///
+/// Returns the new datetime and when it started, to within [`EDGE_POLL`] and a read.
+async fn second_edge(rtc: &mut RtcDS3231) -> Option<(RtcDateTime<Utc>, Instant)> {
+    let start = Instant::now();
+    // The seconds last read unchanged no earlier than this
+    let mut unchanged = start;
+    let first = read_datetime(rtc).await?;
+
+    while start.elapsed() < MAX_EDGE_WAIT {
+        Timer::after(EDGE_POLL).await;
+
+        let read_start = Instant::now();
+        let datetime = read_datetime(rtc).await?;
+        if datetime != first {
+            let window = Instant::now().saturating_duration_since(unchanged);
+            let at = unchanged
+                .checked_add(Duration::from_ticks(window.as_ticks().div_euclid(2)))
+                .unwrap_or(unchanged);
+            return Some((datetime.and_utc().into(), at));
+        }
+        unchanged = read_start;
+    }
+
+    defmt::warn!("[rtc] Seconds did not change. Is the oscillator stopped?");
+    None
+}
+
+async fn read_datetime(rtc: &mut RtcDS3231) -> Option<NaiveDateTime> {
+    rtc.datetime()
+        .await
+        .inspect_err(|err| {
+            defmt::error!(
+                "[rtc] Failed to read datetime: {}",
+                defmt::Debug2Format(err)
+            );
+        })
+        .ok()
+}
+
+#[inline]
+async fn aging_handle(i2c: &mut I2cBus, aging: i8) {
+    if let Err(err) = registers::write_aging_offset(i2c, aging).await {
+        defmt::error!(
+            "[rtc] Failed to set aging offset: {}",
+            defmt::Debug2Format(&err)
+        );
+        return;
+    }
+
+    defmt::info!("[rtc] Aging offset set to {=i8}", aging);
+    DRIFT_LOG.write().await.set_aging(aging);
+}
+
+/// Returns `false` if the datetime could not be set.
+#[inline]
+async fn set_datetime_handle(rtc: &mut RtcDS3231, datetime: RtcDateTime<Utc>) -> bool {
     if let Err(err) = rtc.set_datetime(&datetime.naive_utc()).await {
         defmt::error!(
             "[rtc] Failed to set new datetime: {}",
             defmt::Debug2Format(&err)
         );
+        return false;
     }
+    true
+}
+
+/// Checks if the oscillator stopped while the time is valid.
+#[inline]
+async fn oscillator_stop_handle(i2c: &mut I2cBus) {
+    if !TIME_VALID.load(Ordering::Acquire) {
+        return;
+    }
+
+    match registers::read_oscillator_stop_flag(i2c).await {
+        Ok(true) => mark_time_lost(),
+        Ok(false) => {}
+        Err(err) => defmt::error!(
+            "[rtc] Failed to read oscillator stop flag: {}",
+            defmt::Debug2Format(&err)
+        ),
+    }
+}
+
+/// Marks the time as valid again after it was set.
+///
+/// Clears the oscillator stop flag and re-arms Alarm1, since it was not
+/// rescheduled while the time was lost.
+async fn time_restored_handle(
+    rtc: &mut RtcDS3231,
+    i2c: &mut I2cBus,
+    armed: &mut Option<NaiveDateTime>,
+) {
+    if TIME_VALID.load(Ordering::Acquire) {
+        return;
+    }
+
+    if let Err(err) = registers::clear_oscillator_stop_flag(i2c).await {
+        defmt::error!(
+            "[rtc] Failed to clear oscillator stop flag: {}",
+            defmt::Debug2Format(&err)
+        );
+        return;
+    }
+
+    defmt::info!("[rtc] Time is valid again");
+    // Also clears the warning on the LCD
+    TIME_VALID.store(true, Ordering::Release);
+    schedule_handle(rtc, armed).await;
 }
 
 #[inline]
@@ -89,12 +387,17 @@ async fn time_handle(
                 // If there is a date error, set time to 0
                 defmt::error!("[rtc] Datetime error occured! Resetting clock.");
                 defmt::error!("[rtc] {}", defmt::Debug2Format(&e));
+                mark_time_lost();
 
                 let val = chrono::DateTime::from_timestamp(0, 0).unwrap().into();
                 sender.send(val);
                 return;
             }
-            ds3231::DS3231Error::Alarm(_) => todo!(),
+            ds3231::DS3231Error::Alarm(e) => {
+                defmt::error!("[rtc] Alarm Error occured while reading the time!");
+                defmt::error!("[rtc] {}", defmt::Debug2Format(&e));
+                return;
+            }
         },
     };
 
@@ -0,0 +1,44 @@
+//! # Time Zones
+//! The time zone in use is held in [`TIME_ZONE`], which starts at the
+//! `TZ_RULE` env var and can be changed over the web.
+//!
+//! TZ strings are parsed by [`rusty_clock_core::rtc::tz`].
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use rusty_clock_core::rtc::tz::TzError;
+
+use crate::TZ_OFFSET;
+
+pub(crate) use rusty_clock_core::rtc::tz::{PosixTz, TzString};
+
+/// The time zone in use, along with the TZ string it was parsed from.
+pub(crate) static TIME_ZONE: Mutex<CriticalSectionRawMutex, RefCell<(PosixTz, TzString)>> =
+    Mutex::new(RefCell::new((PosixTz::fixed(TZ_OFFSET), TzString::new())));
+
+/// Sets the time zone from the `TZ_RULE` env var, if set.
+pub(crate) fn init() {
+    let Some(tz) = option_env!("TZ_RULE") else {
+        return;
+    };
+
+    if let Err(err) = set(tz) {
+        defmt::error!("[tz] Invalid `TZ_RULE` env var, using `TZ_OFFSET`: {}", err);
+    }
+}
+
+/// Parses and sets the time zone in use.
+pub(crate) fn set(s: &str) -> Result<(), TzError> {
+    let tz = PosixTz::parse(s)?;
+    let s = TzString::try_from(s).map_err(|_| TzError::TooLong)?;
+
+    TIME_ZONE.lock(|cell| *cell.borrow_mut() = (tz, s));
+    Ok(())
+}
+
+/// Returns the time zone in use.
+#[inline]
+pub(crate) fn current() -> PosixTz {
+    TIME_ZONE.lock(|cell| cell.borrow().0)
+}
@@ -0,0 +1,55 @@
+//! # Countdown Timers
+//! This module manages several labelled countdown timers that
+//! can be paused, resumed and cancelled.
+//!
+//! The timers are held in [`TIMERS`]. Whoever modifies it must
+//! signal [`TIMERS_CHANGED`] so the runner can re-arm itself.
+
+pub(crate) mod table;
+mod task;
+
+use core::fmt::Write as _;
+
+use embassy_executor::Spawner;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, rwlock::RwLock, signal::Signal};
+use embassy_time::Instant;
+use table::TimerTable;
+
+/// All countdown timers.
+pub(crate) static TIMERS: RwLock<CriticalSectionRawMutex, TimerTable> =
+    RwLock::new(TimerTable::new());
+
+/// Wakes the runner after [`TIMERS`] has been modified.
+pub(crate) static TIMERS_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+pub(super) fn init(spawner: Spawner) {
+    spawner.spawn(task::runner_task().unwrap());
+}
+
+/// The most characters [`soonest_countdown`] returns, which is what is left
+/// of the LCD's top line after the time.
+pub(crate) const MAX_COUNTDOWN_LEN: usize = 7;
+
+/// Formats the time left on the running timer that ends the soonest.
+///
+/// Timers of 10 hours and longer drop the seconds to fit in [`MAX_COUNTDOWN_LEN`].
+/// Returns `None` if no timer is running.
+pub(crate) async fn soonest_countdown() -> Option<heapless::String<MAX_COUNTDOWN_LEN>> {
+    let timers = TIMERS.read().await;
+    let secs = timers.soonest()?.remaining(Instant::now()).as_secs();
+
+    let (mins, secs) = (secs.div_euclid(60), secs.rem_euclid(60));
+    let (hours, mins) = (mins.div_euclid(60), mins.rem_euclid(60));
+
+    let mut s = heapless::String::new();
+    // Only truncated for timers longer than 41 days
+    let _ = if hours >= 10 {
+        write!(s, "{hours}h{mins:02}m")
+    } else if hours > 0 {
+        write!(s, "{hours}:{mins:02}:{secs:02}")
+    } else {
+        write!(s, "{mins}:{secs:02}")
+    };
+
+    Some(s)
+}
@@ -0,0 +1,208 @@
+//! # Timer Table
+//! Holds the state of every countdown timer.
+//!
+//! Running timers store when they end, while paused timers store
+//! how much time they have left.
+
+use embassy_time::{Duration, Instant};
+
+/// The maximum number of timers the [`TimerTable`] can hold.
+pub(crate) const MAX_TIMERS: usize = 8;
+
+/// Simply an alias [`heapless::String`] used for timer labels.
+pub(crate) type TimerLabel = heapless::String<16>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub(crate) enum TimerError {
+    #[error("Timer table is full")]
+    TableFull,
+    #[error("No timer found with the given ID")]
+    NotFound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CountdownState {
+    Running { ends: Instant },
+    Paused { remaining: Duration },
+}
+
+#[derive(Debug, Clone)]
+/// A single timer stored in the [`TimerTable`].
+pub(crate) struct Countdown {
+    pub id: u8,
+    pub label: TimerLabel,
+    state: CountdownState,
+}
+
+#[derive(Debug, Clone)]
+/// A snapshot of a [`Countdown`] for reporting.
+pub(crate) struct CountdownStatus {
+    pub id: u8,
+    pub label: TimerLabel,
+    pub remaining_secs: u64,
+    pub paused: bool,
+}
+
+impl Countdown {
+    /// Returns the time left at `now`.
+    #[inline]
+    pub fn remaining(&self, now: Instant) -> Duration {
+        match self.state {
+            CountdownState::Running { ends } => ends.saturating_duration_since(now),
+            CountdownState::Paused { remaining } => remaining,
+        }
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        matches!(self.state, CountdownState::Paused { .. })
+    }
+
+    #[inline]
+    pub fn status(&self, now: Instant) -> CountdownStatus {
+        CountdownStatus {
+            id: self.id,
+            label: self.label.clone(),
+            remaining_secs: self.remaining(now).as_secs(),
+            paused: self.is_paused(),
+        }
+    }
+
+    /// Returns when the timer ends, or `None` if paused.
+    #[inline]
+    fn ends(&self) -> Option<Instant> {
+        match self.state {
+            CountdownState::Running { ends } => Some(ends),
+            CountdownState::Paused { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Holds up to [`MAX_TIMERS`] timers.
+pub(crate) struct TimerTable {
+    timers: heapless::Vec<Countdown, MAX_TIMERS>,
+    /// Label and duration of the last started timer.
+    last: Option<(TimerLabel, Duration)>,
+}
+
+impl TimerTable {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            timers: heapless::Vec::new(),
+            last: None,
+        }
+    }
+
+    #[inline]
+    pub fn timers(&self) -> &[Countdown] {
+        &self.timers
+    }
+
+    #[inline]
+    pub fn get(&self, id: u8) -> Option<&Countdown> {
+        self.timers.iter().find(|t| t.id == id)
+    }
+
+    #[inline]
+    fn get_mut(&mut self, id: u8) -> Result<&mut Countdown, TimerError> {
+        self.timers
+            .iter_mut()
+            .find(|t| t.id == id)
+            .ok_or(TimerError::NotFound)
+    }
+
+    /// Starts a new timer ending `duration` after `now` and returns the ID assigned to it.
+    ///
+    /// The timer is assigned the lowest unused ID.
+    pub fn start(
+        &mut self,
+        label: TimerLabel,
+        duration: Duration,
+        now: Instant,
+    ) -> Result<u8, TimerError> {
+        let id = (0..=u8::MAX)
+            .find(|id| self.get(*id).is_none())
+            .ok_or(TimerError::TableFull)?;
+
+        let ends = now.checked_add(duration).unwrap_or(Instant::MAX);
+
+        self.timers
+            .push(Countdown {
+                id,
+                label: label.clone(),
+                state: CountdownState::Running { ends },
+            })
+            .map_err(|_| TimerError::TableFull)?;
+
+        self.last = Some((label, duration));
+        Ok(id)
+    }
+
+    /// Starts the last started timer again with the same label and duration.
+    pub fn restart_last(&mut self, now: Instant) -> Result<u8, TimerError> {
+        let (label, duration) = self.last.clone().ok_or(TimerError::NotFound)?;
+        self.start(label, duration, now)
+    }
+
+    /// Pauses the timer with the given ID. Does nothing if already paused.
+    pub fn pause(&mut self, id: u8, now: Instant) -> Result<(), TimerError> {
+        let timer = self.get_mut(id)?;
+        timer.state = CountdownState::Paused {
+            remaining: timer.remaining(now),
+        };
+        Ok(())
+    }
+
+    /// Resumes the timer with the given ID. Does nothing if already running.
+    pub fn resume(&mut self, id: u8, now: Instant) -> Result<(), TimerError> {
+        let timer = self.get_mut(id)?;
+        if let CountdownState::Paused { remaining } = timer.state {
+            timer.state = CountdownState::Running {
+                ends: now.checked_add(remaining).unwrap_or(Instant::MAX),
+            };
+        }
+        Ok(())
+    }
+
+    /// Removes the timer with the given ID.
+    pub fn cancel(&mut self, id: u8) -> Result<Countdown, TimerError> {
+        let idx = self
+            .timers
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or(TimerError::NotFound)?;
+
+        Ok(self.timers.swap_remove(idx))
+    }
+
+    /// Returns when the next running timer ends.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.timers.iter().filter_map(Countdown::ends).min()
+    }
+
+    /// Returns the running timer that ends the soonest.
+    pub fn soonest(&self) -> Option<&Countdown> {
+        self.timers
+            .iter()
+            .filter(|t| !t.is_paused())
+            .min_by_key(|t| t.ends())
+    }
+
+    /// Removes all running timers that have ended by `now` and returns them.
+    pub fn expire(&mut self, now: Instant) -> heapless::Vec<Countdown, MAX_TIMERS> {
+        let mut expired = heapless::Vec::new();
+
+        self.timers.retain(|t| {
+            let done = t.ends().is_some_and(|ends| ends <= now);
+            if done {
+                // Cannot overflow since both hold at most `MAX_TIMERS`
+                let _ = expired.push(t.clone());
+            }
+            !done
+        });
+
+        expired
+    }
+}
@@ -0,0 +1,38 @@
+use defmt::info;
+use embassy_futures::select::{Either, select};
+use embassy_time::{Instant, Timer};
+
+use super::{TIMERS, TIMERS_CHANGED};
+use crate::buzzer::{RING_COMMANDS, RingAction, ring::RingSource};
+
+#[embassy_executor::task]
+/// Waits for the next running timer to end and rings the buzzer.
+///
+/// Re-arms whenever [`TIMERS_CHANGED`] is signalled.
+pub(super) async fn runner_task() -> ! {
+    info!("[timer] Listening for timers");
+
+    loop {
+        let next = TIMERS.read().await.next_due();
+
+        let Some(at) = next else {
+            TIMERS_CHANGED.wait().await;
+            continue;
+        };
+
+        if let Either::Second(()) = select(Timer::at(at), TIMERS_CHANGED.wait()).await {
+            continue;
+        }
+
+        let expired = TIMERS.write().await.expire(Instant::now());
+        for timer in &expired {
+            info!("[timer] Timer {} ({=str}) done", timer.id, timer.label);
+        }
+
+        if !expired.is_empty() {
+            RING_COMMANDS
+                .send(RingAction::Start(RingSource::Timer))
+                .await;
+        }
+    }
+}
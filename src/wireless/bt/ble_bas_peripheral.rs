@@ -6,7 +6,11 @@ use embassy_time::Timer;
 use trouble_host::prelude::*;
 
 use super::{BleController, BleStack};
-use crate::{buzzer::BUZZER_ACTION_SIGNAL, rtc_ds3231::TIME_WATCH, utils::mk_static};
+use crate::{
+    buzzer::{BUZZER_COMMANDS, BUZZER_VOLUME, BuzzerAction},
+    rtc_ds3231::TIME_WATCH,
+    utils::mk_static,
+};
 
 #[embassy_executor::task]
 pub(super) async fn run_peripheral(
@@ -55,6 +59,7 @@ async fn gatt_events_task(
     let level = server.battery_service.level;
     let time_epoch_char = server.time_service.epoch;
     let buzzer = server.buzzer_service.level;
+    let volume = server.buzzer_service.volume;
 
     let mut recv = TIME_WATCH
         .receiver()
@@ -79,18 +84,40 @@ async fn gatt_events_task(
                 GattConnectionEvent::Gatt { event } => {
                     match &event {
                         GattEvent::Read(event) => {
-                            server_get!(level, time_epoch_char, buzzer; event, server);
+                            if event.handle() == volume.handle {
+                                // The volume may have been changed elsewhere
+                                let current =
+                                    BUZZER_VOLUME.load(core::sync::atomic::Ordering::Acquire);
+                                if server.set(&volume, &current).is_err() {
+                                    warn!("[gatt] error updating volume");
+                                }
+                            }
+                            server_get!(level, time_epoch_char, buzzer, volume; event, server);
                         }
                         GattEvent::Write(event) => {
                             // server_write!(level, epoch, buzzer; event, server);
                             if event.handle() == buzzer.handle {
                                 let buzzer_action = server.get(&buzzer).unwrap();
 
-                                BUZZER_ACTION_SIGNAL.signal(if buzzer_action {
-                                    crate::buzzer::BuzzerAction::On
+                                let action = if buzzer_action {
+                                    BuzzerAction::On
+                                } else {
+                                    BuzzerAction::Off
+                                };
+                                BUZZER_COMMANDS.send(action.into()).await;
+                            }
+
+                            if event.handle() == volume.handle {
+                                let vol = server.get(&volume).unwrap();
+
+                                // Same range as `/volume`
+                                if vol <= 100 {
+                                    BUZZER_COMMANDS
+                                        .send(BuzzerAction::SetVolume(vol).into())
+                                        .await;
                                 } else {
-                                    crate::buzzer::BuzzerAction::Off
-                                });
+                                    warn!("[gatt] volume out of range: {}", vol);
+                                }
                             }
                         }
                         _ => {}
@@ -43,6 +43,11 @@ pub(super) struct BuzzerService {
     #[characteristic(uuid = characteristic::AUDIO_OUTPUT_DESCRIPTION, read, write, notify)]
     pub level: bool,
 
+    #[descriptor(uuid = descriptors::VALID_RANGE, read, value = [0, 100])]
+    #[characteristic(uuid = "518813df-5dd4-1f87-ec11-cdb001100000", read, write)]
+    /// Volume as a percentage
+    pub volume: u8,
+
     #[characteristic(uuid = "508813df-5dd4-1f87-ec11-cdb001100000", write, read)]
     pub status: bool,
 }
@@ -1,4 +1,5 @@
 pub mod dns;
+pub mod ntp_server;
 pub mod sntp;
 pub mod web_server;
 
@@ -32,13 +33,16 @@ pub(super) fn init(spawner: Spawner, wifi: esp_hal::peripherals::WIFI<'static>)
     spawner.spawn(runner_task(net_runner).unwrap());
     spawner.spawn(connect_to_wifi(wifi_controller).unwrap());
     spawner.spawn(sntp::init(net_stack).unwrap());
+    if ntp_server::NTP_SERVER_ENABLE {
+        spawner.spawn(ntp_server::server_task(net_stack).unwrap());
+    }
 
     web_server::init(spawner, net_stack);
 }
 
-// 3 web tasks + 1 sntp + ? + ?
-// Currently requires 6 sockets minimum. Picoserve possibly adds 2 sockets?
-const MAX_NET_SOCKETS: usize = web_server::WEB_TASK_POOL_SIZE + 3;
+// 3 web tasks + 1 sntp + 1 ntp server + ? + ?
+// Currently requires 7 sockets minimum. Picoserve possibly adds 2 sockets?
+const MAX_NET_SOCKETS: usize = web_server::WEB_TASK_POOL_SIZE + 4;
 
 fn get_stack(
     wifi_interface: esp_radio::wifi::Interfaces<'_>,
@@ -0,0 +1,98 @@
+//! # SNTP Server
+//! Serves the RTC time to the LAN on UDP port 123, e.g. for devices without internet access.
+//!
+//! The stratum follows the last NTP sync. If the RTC lost its time, responses
+//! are marked as unsynchronised so clients ignore them.
+
+use core::sync::atomic::Ordering;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use rusty_clock_core::ntp::{
+    packet::Source,
+    server::{ServeError, Served, serve_one},
+};
+use sntpc_net_embassy::UdpSocketWrapper;
+use static_cell::ConstStaticCell;
+
+use super::sntp::SYNC_STATUS;
+use crate::rtc_ds3231::{TIME_VALID, TIME_WATCH};
+
+/// Whether the SNTP server is started.
+pub(super) const NTP_SERVER_ENABLE: bool = {
+    let s = option_env!("NTP_SERVER_ENABLE").unwrap_or("0");
+    1 == u8::from_str_radix(s, 10)
+        .ok()
+        .expect("Failed to parse .env: NTP_SERVER_ENABLE")
+};
+
+const NTP_LISTEN_PORT: u16 = 123;
+
+static UDP_RX_META: ConstStaticCell<[PacketMetadata; 4]> =
+    ConstStaticCell::new([PacketMetadata::EMPTY; _]);
+static UDP_TX_META: ConstStaticCell<[PacketMetadata; 4]> =
+    ConstStaticCell::new([PacketMetadata::EMPTY; _]);
+static UDP_RX_BUFFER: ConstStaticCell<[u8; 256]> = ConstStaticCell::new([0; _]);
+static UDP_TX_BUFFER: ConstStaticCell<[u8; 256]> = ConstStaticCell::new([0; _]);
+
+#[embassy_executor::task]
+// Task should only be spawned once
+pub(super) async fn server_task(net_stack: embassy_net::Stack<'static>) -> ! {
+    let mut udp_socket = UdpSocket::new(
+        net_stack,
+        UDP_RX_META.take(),
+        UDP_RX_BUFFER.take(),
+        UDP_TX_META.take(),
+        UDP_TX_BUFFER.take(),
+    );
+    udp_socket
+        .bind(NTP_LISTEN_PORT)
+        .expect("[ntp-server] Failed to bind port");
+    defmt::info!("[ntp-server] Listening on port {=u16}", NTP_LISTEN_PORT);
+
+    let udp_socket = UdpSocketWrapper::new(udp_socket);
+
+    // Requests may have extension fields, which are ignored
+    let mut buf = [0; 128];
+    loop {
+        match serve_one(&udp_socket, &mut buf, current_time).await {
+            Ok(Served::Responded(client)) => {
+                defmt::trace!("[ntp-server] Responded to {}", client);
+            }
+            Ok(Served::Ignored(client)) => {
+                defmt::debug!("[ntp-server] Ignored packet from {}", client);
+            }
+            Err(ServeError::Receive(err)) => {
+                defmt::warn!("[ntp-server] Failed to receive: {}", err);
+            }
+            Err(ServeError::Respond(err)) => {
+                defmt::warn!("[ntp-server] Failed to respond: {}", err);
+            }
+        }
+    }
+}
+
+/// The current Unix timestamp of the RTC and where it comes from.
+async fn current_time() -> (i64, Source) {
+    let Some(now) = TIME_WATCH.anon_receiver().try_get() else {
+        return (0, Source::Unsynchronised);
+    };
+
+    (now.timestamp(), current_source().await)
+}
+
+/// Where the RTC time comes from, based on the last NTP sync.
+async fn current_source() -> Source {
+    if !TIME_VALID.load(Ordering::Acquire) {
+        return Source::Unsynchronised;
+    }
+
+    let status = SYNC_STATUS.read().await;
+    match (status.last_success, status.last_source) {
+        (Some(synced_at), Some((server, stratum))) => Source::Upstream {
+            server,
+            stratum,
+            synced_at: synced_at.timestamp(),
+            error_us: status.last_error_us.unwrap_or(0),
+        },
+        _ => Source::Local,
+    }
+}
@@ -1,174 +0,0 @@
-use core::net::SocketAddr;
-use embassy_net::udp::{PacketMetadata, UdpSocket};
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
-use embassy_time::{Duration, WithTimeout as _};
-use sntpc::NtpContext;
-use sntpc_net_embassy::UdpSocketWrapper;
-use static_cell::ConstStaticCell;
-
-use crate::rtc_ds3231::{RTC_COMMANDS, RtcCommand, TIME_WATCH, rtc_time::RtcDateTime};
-
-pub(crate) static NTP_SYNC_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
-
-/// Default NTP server to ping.
-const NTP_SERVER_ADDR: &str = option_env!("NTP_SERVER_ADDR").unwrap_or("pool.ntp.org");
-
-const NTP_SERVER_PORT: u16 = {
-    let port = option_env!("SNTP_PORT").unwrap_or("123");
-    u16::from_str_radix(port, 10)
-        .ok()
-        .expect("Failed to parse .env: SNTP_PORT")
-};
-
-#[derive(Copy, Clone, Default)]
-/// Time in us.
-struct SntpTimestamp(u64);
-
-impl sntpc::NtpTimestampGenerator for SntpTimestamp {
-    fn init(&mut self) {}
-
-    #[expect(clippy::integer_division, reason = "Some precision loss okay here")]
-    fn timestamp_sec(&self) -> u64 {
-        self.0 / 1_000_000
-    }
-    fn timestamp_subsec_micros(&self) -> u32 {
-        (self.0 % 1_000_000).truncate()
-    }
-}
-
-// NOTE: Using `ConstStaticCell` means these buffers are stored in .bss, thus does
-// not take up any flash space.
-static UDP_RX_META: ConstStaticCell<[PacketMetadata; 16]> =
-    ConstStaticCell::new([PacketMetadata::EMPTY; _]);
-static UDP_TX_META: ConstStaticCell<[PacketMetadata; 16]> =
-    ConstStaticCell::new([PacketMetadata::EMPTY; _]);
-static UDP_RX_BUFFER: ConstStaticCell<[u8; 1024]> = ConstStaticCell::new([0; _]);
-static UDP_TX_BUFFER: ConstStaticCell<[u8; 1024]> = ConstStaticCell::new([0; _]);
-
-#[embassy_executor::task]
-// Task should only be spawned once
-pub(crate) async fn init(net_stack: embassy_net::Stack<'static>) -> ! {
-    let udp_rx_meta = UDP_RX_META.take();
-    let udp_tx_meta = UDP_TX_META.take();
-    let udp_tx_buffer = UDP_TX_BUFFER.take();
-    let udp_rx_buffer = UDP_RX_BUFFER.take();
-
-    let mut udp_socket = UdpSocket::new(
-        net_stack,
-        udp_rx_meta,
-        udp_rx_buffer,
-        udp_tx_meta,
-        udp_tx_buffer,
-    );
-
-    // TODO: Check if needed
-    udp_socket.bind(NTP_SERVER_PORT).unwrap();
-    let wrapper = UdpSocketWrapper::new(udp_socket);
-
-    const SYNC_NTP_ON_BOOT: bool = {
-        let s = env!("SYNC_NTP_ON_BOOT");
-        1 == u8::from_str_radix(s, 10)
-            .ok()
-            .expect("Failed to parse env: `SYNC_NTP_ON_BOOT`")
-    };
-
-    if SYNC_NTP_ON_BOOT {
-        NTP_SYNC_SIGNAL.signal(());
-    }
-
-    loop {
-        NTP_SYNC_SIGNAL.wait().await;
-        defmt::info!("[sntp] Syncing RTC with NTP");
-        fetch_sntp_inner(net_stack, &wrapper).await;
-    }
-}
-
-async fn fetch_sntp_inner(
-    net_stack: embassy_net::Stack<'static>,
-    udp_socket: &UdpSocketWrapper<'_>,
-) {
-    defmt::trace!("[sntp] Waiting for Network Link...");
-    if let Ok(()) = net_stack
-        .wait_link_up()
-        .with_timeout(Duration::from_secs(180))
-        .await
-    {
-        defmt::info!("[sntp] Network Link is Up!");
-    } else {
-        defmt::warn!("[sntp] Network Link Timed Out!");
-        return;
-    }
-
-    defmt::trace!("[sntp] Waiting to get IP address...");
-
-    if let Ok(()) = net_stack
-        .wait_config_up()
-        .with_timeout(Duration::from_secs(180))
-        .await
-    {
-        // SAFETY: We just awaited for the config to be up
-        unsafe {
-            let config = net_stack.config_v4().unwrap_unchecked();
-            defmt::info!("[sntp] Got IP: {}", config.address);
-        }
-    } else {
-        defmt::warn!("[sntp] DHCP IP Address Request Timed Out!");
-        return;
-    }
-
-    let addr = match super::dns::resolve(NTP_SERVER_ADDR, net_stack).await {
-        Ok(addrs) => addrs,
-        Err(err) => {
-            defmt::warn!("[sntp] DNS Error Received: {}", err);
-            return;
-        }
-    };
-
-    let mut recv = TIME_WATCH
-        .receiver()
-        .expect("[sntp] Max `TIME_WATCH` rx reached");
-
-    defmt::debug!("[sntp] Sending SNTP Request...");
-    let current_timestamp = recv.get().await.timestamp_micros();
-
-    let result = sntpc::get_time(
-        SocketAddr::from((addr, NTP_SERVER_PORT)),
-        udp_socket,
-        NtpContext::new(SntpTimestamp(current_timestamp.cast_unsigned())),
-    )
-    .await;
-
-    defmt::info!("[sntp] Received a response!");
-
-    // Change RTC time to match NTP response
-    match result {
-        Ok(time) => {
-            #[cfg(debug_assertions)]
-            defmt::debug!("[sntp] Response: {}", time);
-
-            defmt::info!("[sntp] Setting RTC Datetime to NTP...");
-            let datetime = RtcDateTime::from_timestamp(time.seconds.cast_signed());
-
-            RTC_COMMANDS
-                .send(RtcCommand::SetDateTime(datetime).into())
-                .await;
-
-            #[cfg(debug_assertions)]
-            {
-                let rtc_time = recv.get().await.to_timestamp();
-                defmt::debug!("[sntp] NTP: {=u64}", time.seconds);
-                defmt::debug!("[sntp] RTC: {=u64}", rtc_time);
-
-                let diff = u64::from(time.seconds).saturating_sub(rtc_time);
-                defmt::debug!("[sntp] Difference: {=u64}", diff);
-            }
-
-            defmt::info!("[sntp] Succesfully Set RTC Datetime!");
-        }
-        Err(e) => {
-            defmt::warn!("[sntp] NTP Error: {}", e);
-        }
-    }
-
-    defmt::debug!("[sntp] Task Complete!");
-}
@@ -0,0 +1,561 @@
+use chrono::Utc;
+use core::net::{IpAddr, SocketAddr};
+use embassy_futures::select::{Either, select};
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex, rwlock::RwLock, signal::Signal, watch,
+};
+use embassy_time::{Duration, Instant, Timer, WithTimeout as _};
+use esp_hal::rng::Rng;
+use rusty_clock_core::ntp::{
+    kiss::ServerState,
+    sample::{self, Sample},
+};
+use sntpc::NtpContext;
+use sntpc_net_embassy::UdpSocketWrapper;
+use static_cell::ConstStaticCell;
+
+use crate::rtc_ds3231::{
+    DATETIME_SET_SIGNAL, DRIFT_LOG, RTC_COMMANDS, RtcCommand, SECOND_EDGE_SIGNAL, TIME_LOST_SIGNAL,
+    TIME_WATCH, rtc_time::RtcDateTime,
+};
+
+pub(crate) static NTP_SYNC_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// The outcome of the NTP syncs so far.
+pub(crate) static SYNC_STATUS: RwLock<CriticalSectionRawMutex, SyncStatus> =
+    RwLock::new(SyncStatus {
+        last_success: None,
+        last_result: None,
+        last_error_us: None,
+        last_source: None,
+        next_attempt: None,
+        failures: 0,
+        servers: [ServerState::Usable; MAX_SERVERS],
+    });
+
+/// The most servers in [`NTP_SERVER_ADDR`] that are used.
+const MAX_SERVERS: usize = 4;
+
+/// How many requests are sent to each server.
+const SAMPLES_PER_SERVER: usize = 3;
+
+const MAX_SAMPLES: usize = MAX_SERVERS * SAMPLES_PER_SERVER;
+
+/// How long to wait for each response.
+const SAMPLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The least time left before the second boundary the RTC is set on,
+/// so the command is received before it passes.
+const MIN_SET_LEAD: Duration = Duration::from_millis(50);
+
+/// Stratum 16 means the server is not synced.
+const MAX_STRATUM: u8 = 15;
+
+/// How often the RTC is synced with NTP. Periodic syncs are disabled if 0.
+const NTP_SYNC_INTERVAL_MINS: u64 = {
+    let mins = option_env!("NTP_SYNC_INTERVAL_MINS").unwrap_or("360");
+    u64::from_str_radix(mins, 10)
+        .ok()
+        .expect("Failed to parse .env: NTP_SYNC_INTERVAL_MINS")
+};
+
+// TEST: At most a week, since the DS3231 drifts about a second per week
+static_assertions::const_assert!(NTP_SYNC_INTERVAL_MINS <= 7 * 24 * 60);
+
+/// Syncs are spread by up to this fraction of the interval either way,
+/// so clocks powered on together do not all hit the server at once.
+const JITTER_DIVISOR: u64 = 10;
+
+/// The first retry after a failed sync. Doubles with each failure, up to the interval.
+const MIN_RETRY: Duration = Duration::from_secs(60);
+
+/// Whether the RTC's aging offset is trimmed from the measured drift.
+const RTC_AUTO_TRIM: bool = {
+    let s = option_env!("RTC_AUTO_TRIM").unwrap_or("0");
+    1 == u8::from_str_radix(s, 10)
+        .ok()
+        .expect("Failed to parse .env: RTC_AUTO_TRIM")
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub(crate) enum SyncError {
+    #[error("Network link is down")]
+    LinkDown,
+    #[error("No IP address from DHCP")]
+    NoAddress,
+    #[error("DNS request failed")]
+    Dns,
+    #[error("NTP request failed")]
+    Ntp,
+    #[error("NTP servers disagree")]
+    Inconsistent,
+    #[error("All NTP servers asked us to back off")]
+    Refused,
+}
+
+#[derive(Debug, defmt::Format, thiserror::Error)]
+enum SampleError {
+    #[error("Timed out")]
+    Timeout,
+    #[error("Kiss-o'-death received: {0:?}")]
+    KissOfDeath(sntpc::KissOfDeathCode),
+    #[error("Server is not synced, stratum {0}")]
+    BadStratum(u8),
+    #[error("{0:?}")]
+    Ntp(sntpc::Error),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SyncStatus {
+    /// When the RTC was last set from NTP. [`None`] if it lost its time since.
+    pub last_success: Option<RtcDateTime<Utc>>,
+    /// The result of the last attempt.
+    pub last_result: Option<Result<(), SyncError>>,
+    /// How far off the RTC may have been after the last success, in us.
+    pub last_error_us: Option<u64>,
+    /// Address and stratum of the server of the last success.
+    pub last_source: Option<(IpAddr, u8)>,
+    /// When the next periodic sync is due. [`None`] if disabled.
+    pub next_attempt: Option<RtcDateTime<Utc>>,
+    /// Failed attempts since the last success.
+    pub failures: u8,
+    /// Whether each server in [`NTP_SERVER_ADDR`] may be used, in order.
+    pub servers: [ServerState; MAX_SERVERS],
+}
+
+/// The outcome of a successful sync.
+struct Synced {
+    datetime: RtcDateTime<Utc>,
+    /// Upper bound of the error the RTC was set with, in us.
+    error_us: u64,
+    server: IpAddr,
+    stratum: u8,
+}
+
+/// Comma-separated NTP servers to ping, tried in order.
+const NTP_SERVER_ADDR: &str = option_env!("NTP_SERVER_ADDR").unwrap_or("pool.ntp.org");
+
+const NTP_SERVER_PORT: u16 = {
+    let port = option_env!("SNTP_PORT").unwrap_or("123");
+    u16::from_str_radix(port, 10)
+        .ok()
+        .expect("Failed to parse .env: SNTP_PORT")
+};
+
+/// The RTC time, advanced by [`Instant`] since it was read.
+///
+/// `sntpc` reads the timestamp when sending the request and again when receiving the
+/// response, so it has to keep moving for the round-trip delay and offset to be correct.
+#[derive(Copy, Clone)]
+struct SntpTimestamp {
+    /// RTC time in us when `base` was taken.
+    base_us: u64,
+    base: Instant,
+    now: Instant,
+}
+
+impl SntpTimestamp {
+    fn new(base_us: u64) -> Self {
+        let base = Instant::now();
+        Self {
+            base_us,
+            base,
+            now: base,
+        }
+    }
+
+    /// Time in us.
+    fn micros(&self) -> u64 {
+        self.base_us
+            .saturating_add(self.now.saturating_duration_since(self.base).as_micros())
+    }
+
+    /// Difference between the timestamps and [`Instant`] in us.
+    fn instant_offset_us(&self) -> i64 {
+        i64::try_from(self.base_us)
+            .unwrap_or(i64::MAX)
+            .saturating_sub(i64::try_from(self.base.as_micros()).unwrap_or(i64::MAX))
+    }
+}
+
+impl sntpc::NtpTimestampGenerator for SntpTimestamp {
+    fn init(&mut self) {
+        self.now = Instant::now();
+    }
+
+    fn timestamp_sec(&self) -> u64 {
+        self.micros().div_euclid(1_000_000)
+    }
+    fn timestamp_subsec_micros(&self) -> u32 {
+        self.micros().rem_euclid(1_000_000).truncate()
+    }
+}
+
+// NOTE: Using `ConstStaticCell` means these buffers are stored in .bss, thus does
+// not take up any flash space.
+static UDP_RX_META: ConstStaticCell<[PacketMetadata; 16]> =
+    ConstStaticCell::new([PacketMetadata::EMPTY; _]);
+static UDP_TX_META: ConstStaticCell<[PacketMetadata; 16]> =
+    ConstStaticCell::new([PacketMetadata::EMPTY; _]);
+static UDP_RX_BUFFER: ConstStaticCell<[u8; 1024]> = ConstStaticCell::new([0; _]);
+static UDP_TX_BUFFER: ConstStaticCell<[u8; 1024]> = ConstStaticCell::new([0; _]);
+
+#[embassy_executor::task]
+// Task should only be spawned once
+pub(crate) async fn init(net_stack: embassy_net::Stack<'static>) -> ! {
+    const SYNC_NTP_ON_BOOT: bool = {
+        let s = env!("SYNC_NTP_ON_BOOT");
+        1 == u8::from_str_radix(s, 10)
+            .ok()
+            .expect("Failed to parse env: `SYNC_NTP_ON_BOOT`")
+    };
+
+    let udp_rx_meta = UDP_RX_META.take();
+    let udp_tx_meta = UDP_TX_META.take();
+    let udp_tx_buffer = UDP_TX_BUFFER.take();
+    let udp_rx_buffer = UDP_RX_BUFFER.take();
+
+    let mut udp_socket = UdpSocket::new(
+        net_stack,
+        udp_rx_meta,
+        udp_rx_buffer,
+        udp_tx_meta,
+        udp_tx_buffer,
+    );
+
+    // Any local port, since port 123 may be taken by the NTP server
+    udp_socket.bind(0).unwrap();
+    let wrapper = UdpSocketWrapper::new(udp_socket);
+
+    if SYNC_NTP_ON_BOOT {
+        NTP_SYNC_SIGNAL.signal(());
+    }
+
+    let mut failures: u8 = 0;
+    let mut servers = [ServerState::Usable; MAX_SERVERS];
+    loop {
+        let delay = next_delay(failures);
+        set_next_attempt(delay).await;
+
+        let requested = select(NTP_SYNC_SIGNAL.wait(), TIME_LOST_SIGNAL.wait());
+        let woken = match delay {
+            Some(delay) => select(requested, Timer::after(delay)).await,
+            None => Either::First(requested.await),
+        };
+
+        if let Either::First(Either::Second(())) = woken {
+            defmt::warn!("[sntp] RTC lost its time");
+            // The last sync no longer says anything about the RTC
+            SYNC_STATUS.write().await.last_success = None;
+        }
+
+        defmt::info!("[sntp] Syncing RTC with NTP");
+        let result = fetch_sntp_inner(net_stack, &wrapper, &mut servers).await;
+
+        let mut status = SYNC_STATUS.write().await;
+        match &result {
+            Ok(synced) => {
+                failures = 0;
+                status.last_success = Some(synced.datetime);
+                status.last_error_us = Some(synced.error_us);
+                status.last_source = Some((synced.server, synced.stratum));
+            }
+            Err(_) => failures = failures.saturating_add(1),
+        }
+        status.last_result = Some(result.map(|_| ()));
+        status.failures = failures;
+        status.servers = servers;
+    }
+}
+
+/// Returns how long to wait for the next sync, or [`None`] if periodic syncs are disabled.
+///
+/// After a failure, retries start at [`MIN_RETRY`] and back off exponentially.
+fn next_delay(failures: u8) -> Option<Duration> {
+    if NTP_SYNC_INTERVAL_MINS == 0 {
+        return None;
+    }
+
+    let interval = Duration::from_secs(NTP_SYNC_INTERVAL_MINS.saturating_mul(60));
+    if failures > 0 {
+        let backoff = MIN_RETRY
+            .as_secs()
+            .saturating_mul(1_u64.wrapping_shl(u32::from(failures.saturating_sub(1)).min(16)));
+        return Some(Duration::from_secs(backoff).min(interval));
+    }
+
+    // Somewhere within interval ± interval / JITTER_DIVISOR
+    let spread = interval.as_secs().div_euclid(JITTER_DIVISOR);
+    let offset = u64::from(Rng::new().random())
+        .checked_rem(spread.saturating_mul(2).saturating_add(1))
+        .unwrap_or(0);
+
+    Some(Duration::from_secs(
+        interval
+            .as_secs()
+            .saturating_sub(spread)
+            .saturating_add(offset),
+    ))
+}
+
+/// Records when the next sync is due in [`SYNC_STATUS`].
+async fn set_next_attempt(delay: Option<Duration>) {
+    let next = delay.and_then(|delay| {
+        let now = TIME_WATCH.anon_receiver().try_get()?;
+        let ts = now
+            .timestamp()
+            .saturating_add(delay.as_secs().try_into().ok()?);
+        Some(RtcDateTime::from_timestamp(ts))
+    });
+
+    if let Some(delay) = delay {
+        defmt::debug!("[sntp] Next sync in {=u64}s", delay.as_secs());
+    }
+    SYNC_STATUS.write().await.next_attempt = next;
+}
+
+/// Syncs the RTC with the servers in [`NTP_SERVER_ADDR`] that are `usable`.
+async fn fetch_sntp_inner(
+    net_stack: embassy_net::Stack<'static>,
+    udp_socket: &UdpSocketWrapper<'_>,
+    usable: &mut [ServerState; MAX_SERVERS],
+) -> Result<Synced, SyncError> {
+    defmt::trace!("[sntp] Waiting for Network Link...");
+    if let Ok(()) = net_stack
+        .wait_link_up()
+        .with_timeout(Duration::from_secs(180))
+        .await
+    {
+        defmt::info!("[sntp] Network Link is Up!");
+    } else {
+        defmt::warn!("[sntp] Network Link Timed Out!");
+        return Err(SyncError::LinkDown);
+    }
+
+    defmt::trace!("[sntp] Waiting to get IP address...");
+
+    if let Ok(()) = net_stack
+        .wait_config_up()
+        .with_timeout(Duration::from_secs(180))
+        .await
+    {
+        // SAFETY: We just awaited for the config to be up
+        unsafe {
+            let config = net_stack.config_v4().unwrap_unchecked();
+            defmt::info!("[sntp] Got IP: {}", config.address);
+        }
+    } else {
+        defmt::warn!("[sntp] DHCP IP Address Request Timed Out!");
+        return Err(SyncError::NoAddress);
+    }
+
+    let mut recv = TIME_WATCH
+        .receiver()
+        .expect("[sntp] Max `TIME_WATCH` rx reached");
+
+    let (mut samples, error) = collect_samples(net_stack, udp_socket, &mut recv, usable).await;
+
+    defmt::info!("[sntp] Received {=usize} samples", samples.len());
+    let Some(best) = sample::select(&mut samples) else {
+        defmt::warn!("[sntp] No usable samples");
+        return Err(if samples.is_empty() {
+            error
+        } else {
+            SyncError::Inconsistent
+        });
+    };
+
+    // Must be measured before the RTC is set
+    measure_drift(best.offset_us).await;
+
+    // The sample may be a few seconds old, so it is applied to the current instant
+    let now_us = i64::try_from(Instant::now().as_micros())
+        .unwrap_or(i64::MAX)
+        .saturating_add(best.offset_us);
+
+    // Writing the seconds register starts a new second, so write on the next second boundary
+    let mut next_sec = now_us.div_euclid(1_000_000).saturating_add(1);
+    let lead_us = next_sec.saturating_mul(1_000_000).saturating_sub(now_us);
+    if lead_us < i64::try_from(MIN_SET_LEAD.as_micros()).unwrap_or(i64::MAX) {
+        next_sec = next_sec.saturating_add(1);
+    }
+
+    let at_us = next_sec
+        .saturating_mul(1_000_000)
+        .saturating_sub(best.offset_us);
+    let at = Instant::from_micros(u64::try_from(at_us).unwrap_or(0));
+    let datetime = RtcDateTime::from_timestamp(next_sec);
+
+    defmt::info!("[sntp] Setting RTC Datetime to NTP...");
+    DATETIME_SET_SIGNAL.reset();
+    RTC_COMMANDS
+        .send(RtcCommand::SetDateTimeAt { datetime, at }.into())
+        .await;
+    let late = DATETIME_SET_SIGNAL.wait().await;
+
+    // The offset is only known to within half the round-trip delay
+    let error_us = best
+        .roundtrip_us
+        .div_euclid(2)
+        .saturating_add(late.as_micros());
+
+    // The RTC started the second late, so it is behind by that much
+    DRIFT_LOG.write().await.record_set(
+        next_sec.saturating_mul(1_000_000),
+        i64::try_from(late.as_micros())
+            .unwrap_or(i64::MAX)
+            .saturating_neg(),
+    );
+
+    defmt::info!(
+        "[sntp] Succesfully Set RTC Datetime! Error within {=u64}us",
+        error_us
+    );
+    defmt::debug!("[sntp] Task Complete!");
+    Ok(Synced {
+        datetime,
+        error_us,
+        server: best.server,
+        stratum: best.stratum,
+    })
+}
+
+/// Samples the servers in [`NTP_SERVER_ADDR`] that are `usable` in turn.
+///
+/// Returns the samples along with the error to report if none are usable.
+async fn collect_samples(
+    net_stack: embassy_net::Stack<'static>,
+    udp_socket: &UdpSocketWrapper<'_>,
+    recv: &mut watch::Receiver<'static, CriticalSectionRawMutex, RtcDateTime<Utc>, 3>,
+    usable: &mut [ServerState; MAX_SERVERS],
+) -> (heapless::Vec<Sample, MAX_SAMPLES>, SyncError) {
+    let mut samples = heapless::Vec::<Sample, MAX_SAMPLES>::new();
+    let mut error = SyncError::Refused;
+
+    let servers = NTP_SERVER_ADDR
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .take(MAX_SERVERS);
+
+    for (server, state) in servers.zip(usable.iter_mut()) {
+        if !state.is_usable(Instant::now()) {
+            defmt::debug!("[sntp] Skipping {=str}: {}", server, state);
+            continue;
+        }
+        if error == SyncError::Refused {
+            error = SyncError::Dns;
+        }
+
+        let addr = match super::dns::resolve(server, net_stack).await {
+            Ok(addr) => addr,
+            Err(err) => {
+                defmt::warn!("[sntp] DNS Error Received for {=str}: {}", server, err);
+                continue;
+            }
+        };
+        error = SyncError::Ntp;
+
+        for _ in 0..SAMPLES_PER_SERVER {
+            // Context is only used to check the response, so the RTC time is good enough
+            let current_timestamp = recv.get().await.timestamp_micros();
+
+            match fetch_sample(addr, udp_socket, current_timestamp).await {
+                Ok(sample) => {
+                    defmt::debug!("[sntp] Sample from {=str}: {}", server, sample);
+                    state.answered();
+                    // Cannot fail since each server adds at most `SAMPLES_PER_SERVER`
+                    let _ = samples.push(sample);
+                }
+                Err(SampleError::KissOfDeath(code)) => {
+                    defmt::warn!("[sntp] {=str} sent kiss-o'-death {}", server, code);
+                    // DENY and RSTR drop the server, RATE holds it off for a while
+                    state.kiss(code, Instant::now());
+                    break;
+                }
+                Err(err) => defmt::warn!("[sntp] Bad sample from {=str}: {}", server, err),
+            }
+        }
+    }
+
+    (samples, error)
+}
+
+/// Measures how far off the RTC is from NTP and records its drift.
+///
+/// Trims the aging offset if [`RTC_AUTO_TRIM`] is set.
+async fn measure_drift(offset_us: i64) {
+    SECOND_EDGE_SIGNAL.reset();
+    RTC_COMMANDS.send(RtcCommand::FindSecondEdge.into()).await;
+    let Some((rtc_time, at)) = SECOND_EDGE_SIGNAL.wait().await else {
+        defmt::warn!("[sntp] Failed to measure RTC drift");
+        return;
+    };
+
+    // NTP time when the RTC's second started
+    let ntp_us = i64::try_from(at.as_micros())
+        .unwrap_or(i64::MAX)
+        .saturating_add(offset_us);
+    let error_us = rtc_time
+        .timestamp()
+        .saturating_mul(1_000_000)
+        .saturating_sub(ntp_us);
+    defmt::info!("[sntp] RTC is off by {=i64}us", error_us);
+
+    let mut log = DRIFT_LOG.write().await;
+    if let Some(sample) = log.measure(ntp_us, error_us) {
+        defmt::info!("[sntp] RTC drift: {=i64}ppb", sample.ppb());
+    }
+
+    if !RTC_AUTO_TRIM {
+        return;
+    }
+    let Some(aging) = log.trim() else {
+        return;
+    };
+    drop(log);
+
+    defmt::info!("[sntp] Trimming RTC aging offset to {=i8}", aging);
+    RTC_COMMANDS
+        .send(RtcCommand::SetAgingOffset(aging).into())
+        .await;
+}
+
+/// Sends a single SNTP request and checks the response.
+async fn fetch_sample(
+    addr: IpAddr,
+    udp_socket: &UdpSocketWrapper<'_>,
+    current_timestamp: i64,
+) -> Result<Sample, SampleError> {
+    let timestamp = SntpTimestamp::new(current_timestamp.cast_unsigned());
+    let result = sntpc::get_time(
+        SocketAddr::from((addr, NTP_SERVER_PORT)),
+        udp_socket,
+        NtpContext::new(timestamp),
+    )
+    .with_timeout(SAMPLE_TIMEOUT)
+    .await;
+
+    let time = match result {
+        Ok(Ok(time)) => time,
+        Ok(Err(sntpc::Error::KissOfDeath(code))) => return Err(SampleError::KissOfDeath(code)),
+        Ok(Err(err)) => return Err(SampleError::Ntp(err)),
+        Err(_) => return Err(SampleError::Timeout),
+    };
+
+    #[cfg(debug_assertions)]
+    defmt::debug!("[sntp] Response: {}", time);
+
+    if !(1..=MAX_STRATUM).contains(&time.stratum) {
+        return Err(SampleError::BadStratum(time.stratum));
+    }
+
+    // `sntpc` already accounts for the network delay in the offset to our timestamps
+    Ok(Sample {
+        offset_us: time.offset.saturating_add(timestamp.instant_offset_us()),
+        roundtrip_us: time.roundtrip,
+        server: addr,
+        stratum: time.stratum,
+    })
+}
@@ -1,5 +1,4 @@
-use chrono::Timelike as _;
-use ds3231::Alarm1Config;
+use ds3231::{Alarm1Config, Alarm2Config};
 use picoserve::{
     Router,
     extract::{Form, Query},
@@ -9,8 +8,14 @@ use picoserve::{
 use serde::Deserialize;
 
 use crate::{
-    TZ_OFFSET,
-    rtc_ds3231::{ALARM_CONFIG_RWLOCK, RTC_COMMANDS, RtcCommand},
+    buzzer::{RING_COMMANDS, RING_LOG, RING_STATE, RingAction},
+    rtc_ds3231::{
+        ALARM_CONFIG_RWLOCK, ALARM_TABLE, ALARM2_CONFIG_RWLOCK, MISSED_ALARM, RTC_COMMANDS,
+        RtcCommand, TIME_WATCH, local_alarm,
+        recurrence::Recurrence,
+        schedule::{AlarmEntry, AlarmLabel, ScheduleError},
+        tz::{self, PosixTz},
+    },
 };
 
 #[inline]
@@ -18,6 +23,11 @@ pub(super) fn add_routes(router: Router<impl PathRouter>) -> Router<impl PathRou
     router
         .route("/alarm", get(get_alarm))
         .route("/alarm/clear", get(get_clear_flags))
+        .route("/alarm/missed", get(get_missed_alarm))
+        .route("/alarm/ring", get(get_ring_state))
+        .route("/alarm/unanswered", get(get_unanswered))
+        .route("/alarm/snooze", get(snooze_alarm))
+        .route("/alarm/dismiss", get(dismiss_alarm))
         .route("/alarm/submit", post(set_alarm_form))
         // .route("/alarm/json", post(set_alarm_json))
         .route(
@@ -29,6 +39,25 @@ pub(super) fn add_routes(router: Router<impl PathRouter>) -> Router<impl PathRou
             ),
             get(set_alarm),
         )
+        .route("/alarm2", get(get_alarm2))
+        .route("/alarm2/clear", get(get_clear_alarm2_flags))
+        .route(
+            ("/alarm2/hourly", parse_path_segment::<u8>()),
+            get(set_alarm2_hourly),
+        )
+        .route(
+            (
+                "/alarm2",
+                parse_path_segment::<u8>(),
+                parse_path_segment::<u8>(),
+            ),
+            get(set_alarm2),
+        )
+        .route("/alarms", get(list_alarms).post(create_alarm))
+        .route(
+            ("/alarms", parse_path_segment::<u8>()),
+            get(get_alarm_entry).post(update_alarm).delete(delete_alarm),
+        )
 }
 
 #[derive(Debug, Deserialize)]
@@ -36,50 +65,153 @@ struct AlarmQueryParams {
     pub utc: Option<bool>,
 }
 
+/// Gets Alarm1 in local time, or in UTC as stored in the RTC with `?utc=true`.
 #[inline]
-async fn get_alarm() -> impl IntoResponse {
-    let response = ALARM_CONFIG_RWLOCK.read().await;
-    DebugValue(response)
+async fn get_alarm(Query(query): Query<AlarmQueryParams>) -> impl IntoResponse {
+    let tz = if query.utc.is_some_and(|x| x) {
+        PosixTz::fixed(0)
+    } else {
+        tz::current()
+    };
+
+    let config = ALARM_CONFIG_RWLOCK.read().await;
+    DebugValue(local_alarm::to_local(&config, &tz, now_utc()))
+}
+
+/// Returns the current time in UTC, used to pick the offset of local alarms.
+fn now_utc() -> chrono::NaiveDateTime {
+    TIME_WATCH
+        .anon_receiver()
+        .try_get()
+        .map(|now| now.naive_utc())
+        .unwrap_or_default()
 }
 
 #[inline]
-async fn set_alarm_inner(hour: u8, min: u8, sec: u8, is_utc: bool) {
-    let base_time =
-        chrono::NaiveTime::from_hms_opt(u32::from(hour), u32::from(min), u32::from(sec)).unwrap();
+async fn set_alarm_inner(hour: u8, min: u8, sec: u8, is_utc: bool) -> Result<(), StatusCode> {
+    if hour >= 24 || min >= 60 || sec >= 60 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let conf = Alarm1Config::AtTime {
+        hours: hour,
+        minutes: min,
+        seconds: sec,
+        is_pm: None,
+    };
 
-    let time = if is_utc {
-        base_time
+    let conf = if is_utc {
+        conf
     } else {
-        base_time
-            .overflowing_sub_signed(chrono::TimeDelta::hours(TZ_OFFSET.into()))
-            .0
+        let tz = tz::current();
+        local_alarm::to_utc(&conf, &tz, now_utc()).ok_or(StatusCode::BAD_REQUEST)?
     };
 
     #[cfg(debug_assertions)]
-    defmt::debug!(
-        "{=u32}:{=u32}:{=u32}",
-        time.hour(),
-        time.minute(),
-        time.second()
-    );
-
-    let conf = Alarm1Config::AtTime {
-        hours: time.hour().truncate(),
-        minutes: time.minute().truncate(),
-        seconds: time.second().truncate(),
-        is_pm: None,
-    };
+    defmt::debug!("{}", conf);
 
     RTC_COMMANDS.send(RtcCommand::SetAlarm(conf).into()).await;
+    Ok(())
 }
 
 #[inline]
 async fn set_alarm(
     (hour, min, sec): (u8, u8, u8),
     Query(query): Query<AlarmQueryParams>,
-) -> impl IntoResponse {
-    set_alarm_inner(hour, min, sec, query.utc.is_some_and(|x| x)).await;
-    "Alarm Set!"
+) -> Result<&'static str, StatusCode> {
+    set_alarm_inner(hour, min, sec, query.utc.is_some_and(|x| x)).await?;
+    Ok("Alarm Set!")
+}
+
+#[inline]
+async fn get_missed_alarm() -> impl IntoResponse {
+    let response = MISSED_ALARM.read().await;
+    DebugValue(response)
+}
+
+#[inline]
+async fn get_ring_state() -> impl IntoResponse {
+    let response = RING_STATE.read().await;
+    DebugValue(response)
+}
+
+#[inline]
+async fn get_unanswered() -> impl IntoResponse {
+    let response = RING_LOG.read().await;
+    DebugValue(response)
+}
+
+/// Snoozes the ringing alarm.
+///
+/// Fails if no alarm is ringing or the snooze limit has been reached.
+#[inline]
+async fn snooze_alarm() -> Result<&'static str, StatusCode> {
+    if !RING_STATE.read().await.can_snooze() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    RING_COMMANDS.send(RingAction::Snooze).await;
+    Ok("Alarm Snoozed!")
+}
+
+#[inline]
+async fn dismiss_alarm() -> impl IntoResponse {
+    RING_COMMANDS.send(RingAction::Dismiss).await;
+    "Alarm Dismissed!"
+}
+
+#[inline]
+async fn get_alarm2() -> impl IntoResponse {
+    let response = ALARM2_CONFIG_RWLOCK.read().await;
+    DebugValue(response)
+}
+
+/// Sets Alarm2 to fire daily at the given hour and minute.
+#[inline]
+async fn set_alarm2(
+    (hour, min): (u8, u8),
+    Query(query): Query<AlarmQueryParams>,
+) -> Result<&'static str, StatusCode> {
+    if hour >= 24 || min >= 60 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let conf = Alarm2Config::AtTime {
+        hours: hour,
+        minutes: min,
+        is_pm: None,
+    };
+
+    let conf = if query.utc.is_some_and(|x| x) {
+        conf
+    } else {
+        let tz = tz::current();
+        local_alarm::alarm2_to_utc(&conf, &tz, now_utc()).ok_or(StatusCode::BAD_REQUEST)?
+    };
+
+    RTC_COMMANDS.send(RtcCommand::SetAlarm2(conf).into()).await;
+    Ok("Alarm2 Set!")
+}
+
+/// Sets Alarm2 to fire every hour at the given minute, e.g. for hourly chimes.
+#[inline]
+async fn set_alarm2_hourly(min: u8) -> Result<&'static str, StatusCode> {
+    if min >= 60 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Only differs from UTC in time zones with sub-hour offsets
+    let tz = tz::current();
+    let conf =
+        local_alarm::alarm2_to_utc(&Alarm2Config::AtMinutes { minutes: min }, &tz, now_utc())
+            .ok_or(StatusCode::BAD_REQUEST)?;
+    RTC_COMMANDS.send(RtcCommand::SetAlarm2(conf).into()).await;
+    Ok("Alarm2 Set!")
+}
+
+#[inline]
+async fn get_clear_alarm2_flags() -> impl IntoResponse {
+    RTC_COMMANDS.send(RtcCommand::ClearAlarm2Flags.into()).await;
 }
 
 #[derive(Debug, Deserialize, defmt::Format)]
@@ -128,14 +260,114 @@ async fn set_alarm_form(Form(form): Form<AlarmForm>) -> Result<StatusCode, Statu
 
     if let Some(utc) = is_utc {
         let input: FormCheckbox = utc.try_into().map_err(|()| StatusCode::BAD_REQUEST)?;
-        set_alarm_inner(hour, min, sec, matches!(input, FormCheckbox::On)).await;
+        set_alarm_inner(hour, min, sec, matches!(input, FormCheckbox::On)).await?;
         Ok(StatusCode::OK)
     } else {
-        set_alarm_inner(hour, min, sec, false).await;
+        set_alarm_inner(hour, min, sec, false).await?;
         Ok(StatusCode::OK)
     }
 }
 
+#[derive(Debug, Deserialize, defmt::Format)]
+struct AlarmEntryForm {
+    pub label: AlarmLabel,
+    pub hour: u8,
+    pub min: u8,
+    pub sec: Option<u8>,
+    pub recurrence: heapless::String<64>,
+    pub enabled: Option<heapless::String<3>>,
+}
+
+impl AlarmEntryForm {
+    /// Validates the form and converts it into an [`AlarmEntry`] with the given `id`.
+    fn into_entry(self, id: u8) -> Result<AlarmEntry, StatusCode> {
+        let time = chrono::NaiveTime::from_hms_opt(
+            u32::from(self.hour),
+            u32::from(self.min),
+            u32::from(self.sec.unwrap_or(0)),
+        )
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+        let recurrence: Recurrence = self
+            .recurrence
+            .parse()
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+        let enabled = match self.enabled {
+            Some(s) => FormCheckbox::try_from(s).map_err(|()| StatusCode::BAD_REQUEST)?,
+            None => FormCheckbox::Off,
+        };
+
+        Ok(AlarmEntry {
+            id,
+            label: self.label,
+            enabled: matches!(enabled, FormCheckbox::On),
+            time,
+            recurrence,
+        })
+    }
+}
+
+impl From<ScheduleError> for StatusCode {
+    #[inline]
+    fn from(value: ScheduleError) -> Self {
+        match value {
+            ScheduleError::TableFull => StatusCode::CONFLICT,
+            ScheduleError::NotFound => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+#[inline]
+async fn list_alarms() -> impl IntoResponse {
+    DebugValue(ALARM_TABLE.read().await)
+}
+
+#[inline]
+async fn get_alarm_entry(id: u8) -> Result<impl IntoResponse, StatusCode> {
+    let table = ALARM_TABLE.read().await;
+    let entry = table.get(id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(DebugValue(entry.clone()))
+}
+
+#[inline]
+async fn create_alarm(Form(form): Form<AlarmEntryForm>) -> Result<impl IntoResponse, StatusCode> {
+    #[cfg(debug_assertions)]
+    defmt::debug!("{}", &form);
+
+    // ID is assigned by the table
+    let entry = form.into_entry(0)?;
+    let id = ALARM_TABLE.write().await.insert(
+        entry.label,
+        entry.enabled,
+        entry.time,
+        entry.recurrence,
+    )?;
+
+    RTC_COMMANDS.send(RtcCommand::Reschedule.into()).await;
+    Ok(DebugValue(id))
+}
+
+#[inline]
+async fn update_alarm(id: u8, Form(form): Form<AlarmEntryForm>) -> Result<StatusCode, StatusCode> {
+    #[cfg(debug_assertions)]
+    defmt::debug!("{}", &form);
+
+    let entry = form.into_entry(id)?;
+    ALARM_TABLE.write().await.update(entry)?;
+
+    RTC_COMMANDS.send(RtcCommand::Reschedule.into()).await;
+    Ok(StatusCode::OK)
+}
+
+#[inline]
+async fn delete_alarm(id: u8) -> Result<StatusCode, StatusCode> {
+    ALARM_TABLE.write().await.remove(id)?;
+
+    RTC_COMMANDS.send(RtcCommand::Reschedule.into()).await;
+    Ok(StatusCode::OK)
+}
+
 /// Alarm 1 specific configurations.
 /// 1-to-1 mapping to [`Alarm1Config`], but with serde.
 #[derive(Debug, PartialEq, Deserialize, defmt::Format)]
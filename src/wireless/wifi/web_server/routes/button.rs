@@ -0,0 +1,39 @@
+use picoserve::{
+    Router,
+    response::{DebugValue, IntoResponse},
+    routing::{PathRouter, get, parse_path_segment},
+};
+
+use crate::buzzer::gesture::{BUTTON_MAP, ButtonAction, Gesture};
+
+#[inline]
+pub(super) fn add_routes(router: Router<impl PathRouter>) -> Router<impl PathRouter> {
+    router
+        .route("/button", get(get_button_map))
+        .route("/button/actions", get(list_actions))
+        .route(
+            (
+                "/button",
+                parse_path_segment::<Gesture>(),
+                parse_path_segment::<ButtonAction>(),
+            ),
+            get(set_button_action),
+        )
+}
+
+#[inline]
+async fn get_button_map() -> impl IntoResponse {
+    let response = BUTTON_MAP.read().await;
+    DebugValue(response)
+}
+
+#[inline]
+async fn list_actions() -> impl IntoResponse {
+    DebugValue(ButtonAction::ALL.map(ButtonAction::name))
+}
+
+#[inline]
+async fn set_button_action((gesture, action): (Gesture, ButtonAction)) -> impl IntoResponse {
+    BUTTON_MAP.write().await.set(gesture, action);
+    "Button Action Set!"
+}
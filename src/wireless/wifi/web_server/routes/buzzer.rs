@@ -1,10 +1,17 @@
-use crate::buzzer::{BUZZER_ACTION_SIGNAL, BUZZER_VOLUME, BuzzerAction, IS_BUZZER_ON};
+use crate::{
+    buzzer::{
+        BUZZER_COMMANDS, BUZZER_VOLUME, BuzzerAction, IS_BUZZER_ON,
+        pattern::Pattern,
+        ring::{RING_PATTERNS, RingSource},
+    },
+    pwm::TONE_RANGE_HZ,
+};
 use embassy_time::Timer;
 use picoserve::{
     Router,
     extract::Form,
     response::{DebugValue, IntoResponse, StatusCode},
-    routing::{PathRouter, get, post},
+    routing::{PathRouter, get, parse_path_segment, post},
 };
 
 #[derive(serde::Deserialize)]
@@ -40,6 +47,24 @@ pub(super) fn add_routes(router: Router<impl PathRouter>) -> Router<impl PathRou
             "/buzzer/stream",
             get(async || picoserve::response::EventStream(BuzzerEvent)),
         )
+        .route("/buzzer/patterns", get(list_patterns))
+        .route(
+            ("/buzzer/play", parse_path_segment::<Pattern>()),
+            get(play_pattern),
+        )
+        .route(
+            ("/buzzer/tone", parse_path_segment::<u32>()),
+            get(play_tone),
+        )
+        .route("/buzzer/pattern", get(get_ring_patterns))
+        .route(
+            ("/buzzer/pattern/alarm", parse_path_segment::<Pattern>()),
+            get(async |pattern: Pattern| set_ring_pattern(RingSource::Alarm, pattern).await),
+        )
+        .route(
+            ("/buzzer/pattern/timer", parse_path_segment::<Pattern>()),
+            get(async |pattern: Pattern| set_ring_pattern(RingSource::Timer, pattern).await),
+        )
         .route("/volume", post(post_volume))
 }
 
@@ -51,15 +76,57 @@ async fn get_buzzer() -> impl IntoResponse {
 
 #[inline]
 async fn toggle_buzzer() -> impl IntoResponse {
-    BUZZER_ACTION_SIGNAL.signal(BuzzerAction::Toggle);
+    BUZZER_COMMANDS.send(BuzzerAction::Toggle.into()).await;
 }
 #[inline]
 async fn toggle_buzzer_on() -> impl IntoResponse {
-    BUZZER_ACTION_SIGNAL.signal(BuzzerAction::On);
+    BUZZER_COMMANDS.send(BuzzerAction::On.into()).await;
 }
 #[inline]
 async fn toggle_buzzer_off() -> impl IntoResponse {
-    BUZZER_ACTION_SIGNAL.signal(BuzzerAction::Off);
+    BUZZER_COMMANDS.send(BuzzerAction::Off.into()).await;
+}
+
+#[inline]
+async fn list_patterns() -> impl IntoResponse {
+    DebugValue(Pattern::ALL.map(Pattern::name))
+}
+
+/// Plays a pattern until the buzzer is turned off.
+#[inline]
+async fn play_pattern(pattern: Pattern) -> impl IntoResponse {
+    BUZZER_COMMANDS
+        .send(
+            BuzzerAction::Play {
+                pattern,
+                ramp: false,
+            }
+            .into(),
+        )
+        .await;
+}
+
+/// Plays a tone until the buzzer is turned off. Requires a passive buzzer.
+#[inline]
+async fn play_tone(hz: u32) -> Result<impl IntoResponse, StatusCode> {
+    if !TONE_RANGE_HZ.contains(&hz) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    BUZZER_COMMANDS.send(BuzzerAction::Tone(hz).into()).await;
+    Ok(())
+}
+
+#[inline]
+async fn get_ring_patterns() -> impl IntoResponse {
+    let response = RING_PATTERNS.read().await;
+    DebugValue(response)
+}
+
+#[inline]
+async fn set_ring_pattern(source: RingSource, pattern: Pattern) -> impl IntoResponse {
+    RING_PATTERNS.write().await.set(source, pattern);
+    "Pattern Set!"
 }
 
 #[inline]
@@ -70,13 +137,15 @@ async fn post_volume(Form(form): Form<VolumeForm>) -> impl IntoResponse {
         return Err(StatusCode::BAD_REQUEST);
     }
 
-    BUZZER_ACTION_SIGNAL.signal(BuzzerAction::SetVolume(form.volume));
+    BUZZER_COMMANDS
+        .send(BuzzerAction::SetVolume(form.volume).into())
+        .await;
     #[cfg(debug_assertions)]
     {
         // To see effect of volume while debugging.
         // Should not automatically turn on buzzer at production
         embassy_time::Timer::after_millis(300).await;
-        BUZZER_ACTION_SIGNAL.signal(BuzzerAction::On);
+        BUZZER_COMMANDS.send(BuzzerAction::On.into()).await;
     }
     Ok(())
 }
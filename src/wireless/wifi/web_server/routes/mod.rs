@@ -7,6 +7,7 @@ use picoserve::{
 };
 
 mod alarm;
+mod button;
 mod buzzer;
 #[cfg(debug_assertions)]
 mod debug;
@@ -27,6 +28,7 @@ pub(super) fn add_all_routes(router: Router<impl PathRouter>) -> Router<impl Pat
     let router = add_routes!(
         router;
         alarm,
+        button,
         buzzer,
         time,
         timer,
@@ -49,32 +51,70 @@ GET /                         - Gets Control Panel webpage
 GET /help                     - Prints this help message.
 
 GET /time                     - Gets current time
+POST /time                    - Sets the RTC from an RFC 3339 datetime or UNIX_EPOCH
+GET /time/valid               - Gets whether the time is valid, false if the RTC lost power
 GET /epoch                    - Gets current time as UNIX_EPOCH
 GET /uptime                   - Gets uptime of MCU
 GET /sync                     - Syncs RTC time with NTP
+GET /sync/status              - Gets the last NTP sync, its result and the next one
+GET /sync/drift               - Gets the RTC aging offset and drift history
+GET /tz                       - Gets the time zone rule and current UTC offset
+POST /tz                      - Sets the time zone from a POSIX TZ string
 SSE /time/stream
 
-GET /alarm                    - Gets alarm settings
+GET /alarm                    - Gets alarm settings in local time (?utc=true for UTC)
 GET /alarm/clear              - Clear RTC Flags
+GET /alarm/missed             - Gets alarm missed while powered off
+GET /alarm/ring               - Gets state of the ringing alarm
+GET /alarm/unanswered         - Gets alarms that stopped by themselves
+GET /alarm/snooze             - Snoozes the ringing alarm
+GET /alarm/dismiss            - Dismisses the ringing alarm
 GET /alarm/:hour/:min/:sec    - Sets alarm
 GET /alarm/off                - Turns off alarm if active
 GET /alarm/on
 GET /alarm/toggle
 POST /alarm/submit 
 
+GET /alarm2                   - Gets Alarm2 settings
+GET /alarm2/clear             - Clear Alarm2 Flag
+GET /alarm2/:hour/:min        - Sets Alarm2 daily
+GET /alarm2/hourly/:min       - Sets Alarm2 hourly
+
+GET /alarms                   - Lists all scheduled alarms
+POST /alarms                  - Creates a new alarm
+GET /alarms/:id               - Gets alarm by ID
+POST /alarms/:id              - Updates alarm by ID
+DELETE /alarms/:id            - Deletes alarm by ID
+
 GET /buzzer                   - Gets current buzzer volume
 GET /buzzer/toggle
 GET /buzzer/on
 GET /buzzer/off
+GET /buzzer/patterns          - Lists beep patterns
+GET /buzzer/play/:pattern     - Plays pattern until turned off
+GET /buzzer/tone/:hz          - Plays tone until turned off (passive buzzer)
+GET /buzzer/pattern           - Gets alarm and timer patterns
+GET /buzzer/pattern/alarm/:pattern
+GET /buzzer/pattern/timer/:pattern
 POST /volume
 SSE /buzzer/stream
 
+GET /button                   - Gets action of each button gesture
+GET /button/actions           - Lists button actions
+GET /button/:gesture/:action  - Maps short, long or double press to action
+
 GET /lcd/on
 GET /lcd/off
 GET /lcd/toggle
 POST /lcd/display
 
-GET /timer/:sec               - Set timer in seconds
-POST /timer
+GET /timer/:sec               - Starts a timer in seconds
+POST /timer                   - Starts a labelled timer
+GET /timer/pause/:id          - Pauses timer by ID
+GET /timer/resume/:id         - Resumes timer by ID
+GET /timer/cancel/:id         - Cancels timer by ID
+GET /timers                   - Lists all timers
+GET /timers/:id               - Gets remaining time of timer by ID
+SSE /timer/stream
 "
 }
@@ -1,18 +1,38 @@
 use embassy_time::Timer;
 use picoserve::{
     Router,
-    extract::Query,
-    response::{DebugValue, IntoResponse},
+    extract::{Form, Query},
+    response::{DebugValue, IntoResponse, StatusCode},
     routing::{PathRouter, get},
 };
 
-use crate::{BOOT_TIME, rtc_ds3231::TIME_WATCH, wireless::wifi::sntp::NTP_SYNC_SIGNAL};
+use crate::{
+    BOOT_TIME,
+    rtc_ds3231::{
+        DRIFT_LOG, RTC_COMMANDS, RtcCommand, TIME_VALID, TIME_WATCH,
+        drift::MAX_DRIFT_SAMPLES,
+        rtc_time::RtcDateTime,
+        tz::{self, TIME_ZONE, TzString},
+    },
+    wireless::wifi::sntp::{NTP_SYNC_SIGNAL, SYNC_STATUS},
+};
 
 #[derive(Debug, serde::Deserialize)]
 struct TimeQueryParams {
     pub utc: Option<bool>,
 }
 
+#[derive(Debug, serde::Deserialize)]
+struct TimeForm {
+    /// An RFC 3339 timestamp, a local ISO 8601 datetime or a Unix timestamp.
+    pub time: heapless::String<40>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TzForm {
+    pub tz: TzString,
+}
+
 struct TimeEvent;
 
 impl picoserve::response::sse::EventSource for TimeEvent {
@@ -45,10 +65,14 @@ impl picoserve::response::sse::EventSource for TimeEvent {
 #[inline]
 pub(super) fn add_routes(router: Router<impl PathRouter>) -> Router<impl PathRouter> {
     router
-        .route("/time", get(get_time))
+        .route("/time", get(get_time).post(set_time))
+        .route("/time/valid", get(get_time_valid))
         .route("/epoch", get(get_epoch))
         .route("/sync", get(get_sync))
+        .route("/sync/status", get(get_sync_status))
+        .route("/sync/drift", get(get_sync_drift))
         .route("/uptime", get(get_uptime))
+        .route("/tz", get(get_tz).post(set_tz))
         .route(
             "/time/stream",
             get(async || picoserve::response::EventStream(TimeEvent)),
@@ -88,7 +112,74 @@ async fn get_time(Query(query): Query<TimeQueryParams>) -> impl IntoResponse {
     }
 }
 
+/// Gets whether the RTC time can be trusted, i.e. its oscillator has not stopped since it was set.
+#[inline]
+async fn get_time_valid() -> impl IntoResponse {
+    DebugValue(TIME_VALID.load(core::sync::atomic::Ordering::Acquire))
+}
+
+/// Sets the RTC without NTP, e.g. from the browser's clock.
+///
+/// Alarm1 is rescheduled, since the next alarm may have been skipped over.
+#[inline]
+async fn set_time(Form(form): Form<TimeForm>) -> Result<impl IntoResponse, StatusCode> {
+    let datetime = RtcDateTime::parse(&form.time).map_err(|err| {
+        defmt::warn!("[web] Invalid time: {}", err);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    defmt::info!(
+        "[web] Setting RTC Datetime to {=str}",
+        datetime.to_iso8601()
+    );
+    RTC_COMMANDS
+        .send(RtcCommand::SetDateTime(datetime).into())
+        .await;
+    RTC_COMMANDS.send(RtcCommand::Reschedule.into()).await;
+
+    Ok(DebugValue(datetime.to_iso8601()))
+}
+
 #[inline]
 async fn get_sync() -> impl IntoResponse {
     NTP_SYNC_SIGNAL.signal(());
 }
+
+#[inline]
+async fn get_tz() -> impl IntoResponse {
+    let rule = TIME_ZONE.lock(|cell| cell.borrow().1.clone());
+    let offset = TIME_WATCH
+        .anon_receiver()
+        .try_get()
+        .map(|now| now.local().offset().local_minus_utc());
+
+    DebugValue((rule, offset))
+}
+
+/// Sets the time zone from a POSIX TZ string, e.g. `CET-1CEST,M3.5.0,M10.5.0/3`.
+///
+/// Alarm1 is rescheduled, since the next alarm may now be at a different UTC time.
+#[inline]
+async fn set_tz(Form(form): Form<TzForm>) -> Result<&'static str, StatusCode> {
+    if let Err(err) = tz::set(&form.tz) {
+        defmt::warn!("[web] Invalid time zone: {}", err);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    RTC_COMMANDS.send(RtcCommand::Reschedule.into()).await;
+    Ok("Time Zone Set!")
+}
+
+#[inline]
+async fn get_sync_status() -> impl IntoResponse {
+    DebugValue(*SYNC_STATUS.read().await)
+}
+
+/// Gets the RTC's aging offset, its drift in ppb with that offset, and the drift history.
+#[inline]
+async fn get_sync_drift() -> impl IntoResponse {
+    let log = DRIFT_LOG.read().await;
+    let samples: heapless::Vec<_, MAX_DRIFT_SAMPLES> = log.samples().copied().collect();
+
+    DebugValue((log.aging(), log.drift(), samples))
+}
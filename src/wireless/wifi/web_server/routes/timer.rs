@@ -1,10 +1,16 @@
+use core::fmt::Write as _;
+
+use embassy_time::{Duration, Instant, Timer};
 use picoserve::{
     extract::Form,
-    response::IntoResponse,
+    response::{DebugValue, IntoResponse, StatusCode},
     routing::{get, parse_path_segment, post},
 };
 
-use crate::buzzer::TIMER_SIGNAL;
+use crate::timer::{
+    TIMERS, TIMERS_CHANGED,
+    table::{CountdownStatus, MAX_TIMERS, TimerError, TimerLabel},
+};
 
 #[inline]
 pub(super) fn add_routes(
@@ -13,19 +19,145 @@ pub(super) fn add_routes(
     router
         .route("/timer", post(timer_form))
         .route(("/timer", parse_path_segment::<u32>()), get(set_timer))
+        .route(
+            "/timer/stream",
+            get(async || picoserve::response::EventStream(TimerEvent)),
+        )
+        .route(
+            ("/timer/pause", parse_path_segment::<u8>()),
+            get(pause_timer),
+        )
+        .route(
+            ("/timer/resume", parse_path_segment::<u8>()),
+            get(resume_timer),
+        )
+        .route(
+            ("/timer/cancel", parse_path_segment::<u8>()),
+            get(cancel_timer),
+        )
+        .route("/timers", get(list_timers))
+        .route(("/timers", parse_path_segment::<u8>()), get(get_timer))
+}
+
+struct TimerEvent;
+
+impl picoserve::response::sse::EventSource for TimerEvent {
+    async fn write_events<W: picoserve::io::Write>(
+        self,
+        mut writer: picoserve::response::sse::EventWriter<'_, W>,
+    ) -> Result<(), W::Error> {
+        loop {
+            let statuses = timer_statuses().await;
+
+            let mut data = heapless::String::<256>::new();
+            for status in &statuses {
+                let (mins, secs) = (
+                    status.remaining_secs.div_euclid(60),
+                    status.remaining_secs.rem_euclid(60),
+                );
+                let paused = if status.paused { " (paused)" } else { "" };
+
+                // Only truncated with unreasonably long labels and durations
+                let _ = write!(
+                    data,
+                    "[{}] {} {mins}:{secs:02}{paused}  ",
+                    status.id, status.label
+                );
+            }
+
+            if data.is_empty() {
+                writer.write_event("", "No timers").await?;
+            } else {
+                writer.write_event("", data.as_str()).await?;
+            }
+            Timer::after_secs(1).await;
+        }
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
 struct TimerForm {
     timer: u32,
+    label: Option<TimerLabel>,
+}
+
+impl From<TimerError> for StatusCode {
+    fn from(value: TimerError) -> Self {
+        match value {
+            TimerError::TableFull => StatusCode::CONFLICT,
+            TimerError::NotFound => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+#[inline]
+async fn start_timer(label: TimerLabel, secs: u32) -> Result<impl IntoResponse, StatusCode> {
+    let id = TIMERS
+        .write()
+        .await
+        .start(label, Duration::from_secs(secs.into()), Instant::now())?;
+    TIMERS_CHANGED.signal(());
+
+    Ok(DebugValue(id))
 }
 
 #[inline]
 async fn timer_form(Form(form): Form<TimerForm>) -> impl IntoResponse {
-    TIMER_SIGNAL.signal(form.timer);
+    start_timer(form.label.unwrap_or_default(), form.timer).await
 }
 
 #[inline]
 async fn set_timer(sec: u32) -> impl IntoResponse {
-    TIMER_SIGNAL.signal(sec);
+    start_timer(TimerLabel::new(), sec).await
+}
+
+#[inline]
+async fn pause_timer(id: u8) -> Result<StatusCode, StatusCode> {
+    TIMERS.write().await.pause(id, Instant::now())?;
+    TIMERS_CHANGED.signal(());
+    Ok(StatusCode::OK)
+}
+
+#[inline]
+async fn resume_timer(id: u8) -> Result<StatusCode, StatusCode> {
+    TIMERS.write().await.resume(id, Instant::now())?;
+    TIMERS_CHANGED.signal(());
+    Ok(StatusCode::OK)
+}
+
+#[inline]
+async fn cancel_timer(id: u8) -> Result<StatusCode, StatusCode> {
+    TIMERS.write().await.cancel(id)?;
+    TIMERS_CHANGED.signal(());
+    Ok(StatusCode::OK)
+}
+
+#[inline]
+async fn timer_statuses() -> heapless::Vec<CountdownStatus, MAX_TIMERS> {
+    let now = Instant::now();
+    TIMERS
+        .read()
+        .await
+        .timers()
+        .iter()
+        .map(|t| t.status(now))
+        .collect()
+}
+
+#[inline]
+async fn list_timers() -> impl IntoResponse {
+    DebugValue(timer_statuses().await)
+}
+
+/// Gets the remaining time of a timer.
+#[inline]
+async fn get_timer(id: u8) -> Result<impl IntoResponse, StatusCode> {
+    let status = TIMERS
+        .read()
+        .await
+        .get(id)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .status(Instant::now());
+
+    Ok(DebugValue(status))
 }